@@ -1,25 +1,57 @@
-use std::fmt;
+use std::{collections::BTreeMap, fmt};
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Node {
+    AnonymousFunction(Vec<Node>, Vec<Node>, bool, Option<Type>),
+    Append(String, Box<Node>),
     AssignVariable(String, Box<Node>, Vec<Node>),
+    Assert(Box<Node>, String),
     Binary(BinaryOperation, Box<Node>),
     Boolean(bool),
+    Break,
     CallFunction(String, Vec<Node>),
+    Closure(Box<Node>, BTreeMap<String, Node>),
+    Continue,
     DeclareBoolean(String, Box<Node>),
+    DeclareConstant(Box<Node>),
     DeclareFloat(String, Box<Node>),
-    DeclareFunction(String, Vec<Node>, Vec<Node>, bool),
+    DeclareFunction(String, Vec<Node>, Vec<Node>, bool, Option<Type>),
+    DeclareFunctionVariable(String, Box<Node>),
+    DeclareInteger(String, Box<Node>),
+    DeclareList(String),
+    DeclareMap(String),
+    DeclareRecord(String, Vec<Node>),
     DeclareString(String, Box<Node>),
-    Float(f32),
+    Exit(Box<Node>),
+    Expression(Box<Node>, Vec<Node>),
+    Float(f64),
+    FormatDefault,
+    FormatFixed(Box<Node>),
+    FormatRoman,
+    FormatScientific,
     For(Box<Node>, Box<Node>, Vec<Node>),
+    Function(String),
+    ForEach(Box<Node>, Box<Node>, Vec<Node>),
     If(Box<Node>, Vec<Node>, Vec<Node>),
+    Integer(i64),
+    Interpolation(Vec<Node>),
+    List(Vec<Node>),
     Main(Vec<Node>),
+    Map(BTreeMap<String, Node>),
+    NewRecord(String, String),
     Print(Box<Node>),
+    PrintLine(Box<Node>),
     Return(Box<Node>),
     ReadBoolean(Box<Node>),
     ReadFloat(Box<Node>),
     ReadString(Box<Node>),
+    Record(String, Vec<(String, Node)>),
+    Remove(String, Box<Node>),
+    SetElement(String, Box<Node>, Box<Node>),
+    SetField(String, String, Box<Node>),
     String(String),
+    Try(Vec<Node>, Option<String>, Vec<Node>),
+    TypedParameter(String, Type),
     Unary(UnaryOperation),
     Variable(String),
     While(Box<Node>, Vec<Node>),
@@ -29,6 +61,11 @@ pub enum Node {
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum UnaryOperation {
     Not,
+    Length,
+    ToInteger,
+    ToFloat,
+    ToString,
+    ToBoolean,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -44,16 +81,174 @@ pub enum BinaryOperation {
     LessThan,
     Or,
     And,
+    Index,
+    ContainsKey,
+    Field,
+}
+
+/// The kinds of value a variable can hold. Records are told apart by their type name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Type {
+    Boolean,
+    Float,
+    Integer,
+    String,
+    List,
+    Map,
+    Record(String),
+    Function,
+}
+
+impl Type {
+    /// The type of a value, or None if the node isn't one.
+    pub fn of(node: &Node) -> Option<Type> {
+        match node {
+            Node::Boolean(_) => Some(Type::Boolean),
+            Node::Float(_) => Some(Type::Float),
+            Node::Integer(_) => Some(Type::Integer),
+            Node::String(_) => Some(Type::String),
+            Node::List(_) => Some(Type::List),
+            Node::Map(_) => Some(Type::Map),
+            Node::Record(type_name, _) => Some(Type::Record(type_name.clone())),
+            Node::Function(_) | Node::Closure(_, _) => Some(Type::Function),
+            _ => None,
+        }
+    }
+
+    /// The type a name in a function signature refers to. Anything that isn't built in is a record,
+    /// which the checker makes sure is declared.
+    pub fn named(name: &str) -> Type {
+        match name {
+            "boolean" => Type::Boolean,
+            "float" => Type::Float,
+            "integer" => Type::Integer,
+            "string" => Type::String,
+            "list" => Type::List,
+            "map" => Type::Map,
+            "function" => Type::Function,
+            type_name => Type::Record(type_name.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        match &self {
+            Type::Boolean => write!(f, "boolean"),
+            Type::Float => write!(f, "float"),
+            Type::Integer => write!(f, "integer"),
+            Type::String => write!(f, "string"),
+            Type::List => write!(f, "list"),
+            Type::Map => write!(f, "map"),
+            Type::Record(type_name) => write!(f, "{}", type_name),
+            Type::Function => write!(f, "function"),
+        }
+    }
+}
+
+/// How numbers are written when printed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NumberFormat {
+    Default,
+    Fixed(usize),
+    Scientific,
+    Roman,
+}
+
+/// A value printed with its numbers written in a chosen format.
+pub struct Formatted<'a>(pub &'a Node, pub &'a NumberFormat);
+
+impl fmt::Display for Formatted<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        self.0.fmt_value(f, self.1)
+    }
 }
 
 impl fmt::Display for Node {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        self.fmt_value(f, &NumberFormat::Default)
+    }
+}
+
+impl Node {
+    fn fmt_value(&self, f: &mut fmt::Formatter<'_>, format: &NumberFormat) -> fmt::Result {
         match &self {
-            Node::Float(n) => write!(f, "{}", n),
+            Node::Float(n) => match format {
+                NumberFormat::Default => write!(f, "{}", n),
+                NumberFormat::Fixed(places) => write!(f, "{:.*}", places, n),
+                NumberFormat::Scientific => write!(f, "{:e}", n),
+                NumberFormat::Roman => write!(f, "{}", n),
+            },
+            // Roman numerals only go from I to MMMCMXCIX
+            Node::Integer(n) if *format == NumberFormat::Roman && (1..4000).contains(n) => {
+                write!(f, "{}", to_roman(*n))
+            }
+            Node::Integer(n) => write!(f, "{}", n),
             Node::String(s) => write!(f, "{}", s),
             Node::Boolean(true) => write!(f, "From a certain point of view."),
             Node::Boolean(false) => write!(f, "That's impossible!"),
+            Node::List(items) => {
+                write!(f, "[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    item.fmt_value(f, format)?;
+                }
+                write!(f, "]")
+            }
+            // Keys are sorted, so maps always print the same way
+            Node::Map(entries) => {
+                write!(f, "{{")?;
+                for (i, (key, value)) in entries.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}: ", key)?;
+                    value.fmt_value(f, format)?;
+                }
+                write!(f, "}}")
+            }
+            Node::Record(type_name, fields) => {
+                write!(f, "{} {{ ", type_name)?;
+                for (i, (name, value)) in fields.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}: ", name)?;
+                    value.fmt_value(f, format)?;
+                }
+                write!(f, " }}")
+            }
+            Node::Function(name) => write!(f, "{}", name),
+            Node::Closure(_, _) => write!(f, "anonymous function"),
             _ => unreachable!(),
         }
     }
 }
+
+fn to_roman(mut n: i64) -> String {
+    let numerals = [
+        (1000, "M"),
+        (900, "CM"),
+        (500, "D"),
+        (400, "CD"),
+        (100, "C"),
+        (90, "XC"),
+        (50, "L"),
+        (40, "XL"),
+        (10, "X"),
+        (9, "IX"),
+        (5, "V"),
+        (4, "IV"),
+        (1, "I"),
+    ];
+    let mut roman = String::new();
+    for (value, numeral) in numerals.iter() {
+        while n >= *value {
+            roman.push_str(numeral);
+            n -= value;
+        }
+    }
+    roman
+}
@@ -0,0 +1,425 @@
+use std::{cmp::Ordering, convert::TryFrom};
+
+use crate::{ast::Node, error::Error};
+
+/// A small xorshift generator, so runs given the same seed pick the same numbers everywhere.
+pub struct Random {
+    state: u64,
+}
+
+impl Random {
+    pub fn new(seed: u64) -> Random {
+        // Spread the seed out, since xorshift does poorly with small seeds and can't use zero
+        let mut state = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        state = (state ^ (state >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        state = (state ^ (state >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        state ^= state >> 31;
+        Random {
+            state: if state == 0 { 1 } else { state },
+        }
+    }
+
+    fn next(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    /// A float from 0 up to, but not including, 1.
+    fn next_float(&mut self) -> f64 {
+        (self.next() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+/// Calls the built in function with the given name, or returns None if there isn't one. Arguments
+/// have already been worked out into values. Strings are handled as characters rather than bytes,
+/// so positions and lengths count what a reader would see.
+pub fn call(name: &str, arguments: &[Node], random: &mut Random) -> Option<Result<Node, Error>> {
+    let result = match name {
+        "sqrt" => sqrt(arguments),
+        "sin" => float_function("sin", f64::sin, arguments),
+        "cos" => float_function("cos", f64::cos, arguments),
+        "tan" => float_function("tan", f64::tan, arguments),
+        "floor" => rounding("floor", f64::floor, arguments),
+        "ceil" => rounding("ceil", f64::ceil, arguments),
+        "round" => rounding("round", f64::round, arguments),
+        "abs" => abs(arguments),
+        "min" => pick("min", Ordering::Less, arguments),
+        "max" => pick("max", Ordering::Greater, arguments),
+        "random" => random_float(arguments, random),
+        "randomInteger" => random_integer(arguments, random),
+        "substring" => substring(arguments),
+        "indexOf" => index_of(arguments),
+        "upper" => upper(arguments),
+        "lower" => lower(arguments),
+        "trim" => trim(arguments),
+        "split" => split(arguments),
+        "replace" => replace(arguments),
+        _ => return None,
+    };
+    Some(result)
+}
+
+/// The characters from start up to, but not including, end.
+fn substring(arguments: &[Node]) -> Result<Node, Error> {
+    let (text, start, end) = match arguments {
+        [Node::String(text), start, end] => (text, position(start)?, position(end)?),
+        _ => return Err(argument_error("substring", "a string and two integers")),
+    };
+
+    let length = text.chars().count();
+    if start > end || end > length {
+        return Err(Error::Runtime("Substring out of range".to_string()));
+    }
+    Ok(Node::String(
+        text.chars().skip(start).take(end - start).collect(),
+    ))
+}
+
+/// The position of the first match, or -1 if there isn't one.
+fn index_of(arguments: &[Node]) -> Result<Node, Error> {
+    let (text, search) = match arguments {
+        [Node::String(text), Node::String(search)] => (text, search),
+        _ => return Err(argument_error("indexOf", "two strings")),
+    };
+
+    let index = match text.find(search.as_str()) {
+        Some(byte_index) => text[..byte_index].chars().count() as i64,
+        None => -1,
+    };
+    Ok(Node::Integer(index))
+}
+
+fn upper(arguments: &[Node]) -> Result<Node, Error> {
+    match arguments {
+        [Node::String(text)] => Ok(Node::String(text.to_uppercase())),
+        _ => Err(argument_error("upper", "a string")),
+    }
+}
+
+fn lower(arguments: &[Node]) -> Result<Node, Error> {
+    match arguments {
+        [Node::String(text)] => Ok(Node::String(text.to_lowercase())),
+        _ => Err(argument_error("lower", "a string")),
+    }
+}
+
+fn trim(arguments: &[Node]) -> Result<Node, Error> {
+    match arguments {
+        [Node::String(text)] => Ok(Node::String(text.trim().to_string())),
+        _ => Err(argument_error("trim", "a string")),
+    }
+}
+
+/// Splitting on an empty string gives each character on its own.
+fn split(arguments: &[Node]) -> Result<Node, Error> {
+    let parts: Vec<Node> = match arguments {
+        [Node::String(text), Node::String(separator)] if separator.is_empty() => {
+            text.chars().map(|c| Node::String(c.to_string())).collect()
+        }
+        [Node::String(text), Node::String(separator)] => text
+            .split(separator.as_str())
+            .map(|part| Node::String(part.to_string()))
+            .collect(),
+        _ => return Err(argument_error("split", "two strings")),
+    };
+    Ok(Node::List(parts))
+}
+
+fn replace(arguments: &[Node]) -> Result<Node, Error> {
+    match arguments {
+        [Node::String(text), Node::String(from), Node::String(to)] if !from.is_empty() => {
+            Ok(Node::String(text.replace(from.as_str(), to)))
+        }
+        [Node::String(_), Node::String(_), Node::String(_)] => {
+            Err(Error::Runtime("Cannot replace an empty string".to_string()))
+        }
+        _ => Err(argument_error("replace", "three strings")),
+    }
+}
+
+fn sqrt(arguments: &[Node]) -> Result<Node, Error> {
+    match arguments {
+        [value] => match number(value) {
+            Some(value) if value < 0.0 => Err(Error::Runtime(
+                "Cannot take the square root of a negative number".to_string(),
+            )),
+            Some(value) => Ok(Node::Float(value.sqrt())),
+            None => Err(argument_error("sqrt", "a number")),
+        },
+        _ => Err(argument_error("sqrt", "a number")),
+    }
+}
+
+/// Angles are in radians.
+fn float_function<F>(name: &str, function: F, arguments: &[Node]) -> Result<Node, Error>
+where
+    F: Fn(f64) -> f64,
+{
+    match arguments {
+        [value] => match number(value) {
+            Some(value) => Ok(Node::Float(function(value))),
+            None => Err(argument_error(name, "a number")),
+        },
+        _ => Err(argument_error(name, "a number")),
+    }
+}
+
+/// Integers are already whole, so they are given back as they are.
+fn rounding<F>(name: &str, function: F, arguments: &[Node]) -> Result<Node, Error>
+where
+    F: Fn(f64) -> f64,
+{
+    match arguments {
+        [Node::Integer(integer)] => Ok(Node::Integer(*integer)),
+        [Node::Float(float)] => Ok(Node::Float(function(*float))),
+        _ => Err(argument_error(name, "a number")),
+    }
+}
+
+fn abs(arguments: &[Node]) -> Result<Node, Error> {
+    match arguments {
+        [Node::Integer(integer)] => match integer.checked_abs() {
+            Some(integer) => Ok(Node::Integer(integer)),
+            None => Err(Error::Runtime("Integer overflow".to_string())),
+        },
+        [Node::Float(float)] => Ok(Node::Float(float.abs())),
+        _ => Err(argument_error("abs", "a number")),
+    }
+}
+
+/// Picks the smaller or larger of two numbers. Like math operations, two integers give an integer
+/// and anything else gives a float.
+fn pick(name: &str, wanted: Ordering, arguments: &[Node]) -> Result<Node, Error> {
+    match arguments {
+        [Node::Integer(x), Node::Integer(y)] => {
+            Ok(Node::Integer(if x.cmp(y) == wanted { *x } else { *y }))
+        }
+        [x, y] => match (number(x), number(y)) {
+            (Some(x), Some(y)) => {
+                let x_wanted = x.partial_cmp(&y) == Some(wanted);
+                Ok(Node::Float(if x_wanted || y.is_nan() { x } else { y }))
+            }
+            _ => Err(argument_error(name, "two numbers")),
+        },
+        _ => Err(argument_error(name, "two numbers")),
+    }
+}
+
+fn random_float(arguments: &[Node], random: &mut Random) -> Result<Node, Error> {
+    match arguments {
+        [] => Ok(Node::Float(random.next_float())),
+        _ => Err(argument_error("random", "no arguments")),
+    }
+}
+
+/// An integer from low up to and including high.
+fn random_integer(arguments: &[Node], random: &mut Random) -> Result<Node, Error> {
+    let (low, high) = match arguments {
+        [Node::Integer(low), Node::Integer(high)] => (*low, *high),
+        _ => return Err(argument_error("randomInteger", "two integers")),
+    };
+    if low > high {
+        return Err(Error::Runtime(
+            "The lowest random integer cannot be above the highest".to_string(),
+        ));
+    }
+
+    // Worked out in 128 bits, since the range of all integers doesn't fit in 64
+    let range = (high as i128 - low as i128 + 1) as u128;
+    let offset = (random.next() as u128 % range) as i128;
+    Ok(Node::Integer((low as i128 + offset) as i64))
+}
+
+fn number(node: &Node) -> Option<f64> {
+    match node {
+        Node::Float(float) => Some(*float),
+        Node::Integer(integer) => Some(*integer as f64),
+        _ => None,
+    }
+}
+
+fn position(node: &Node) -> Result<usize, Error> {
+    match node {
+        Node::Integer(integer) => usize::try_from(*integer)
+            .map_err(|_| Error::Runtime("Position cannot be negative".to_string())),
+        _ => Err(Error::Type("Position must be an integer".to_string())),
+    }
+}
+
+fn argument_error(name: &str, expected: &str) -> Error {
+    Error::Type(format!("{} takes {}", name, expected))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn string(text: &str) -> Node {
+        Node::String(text.to_string())
+    }
+
+    #[test]
+    fn strings() {
+        let mut random = Random::new(0);
+        assert_eq!(
+            call(
+                "substring",
+                &[string("Mos Eisley ☀"), Node::Integer(4), Node::Integer(12)],
+                &mut random
+            ),
+            Some(Ok(string("Eisley ☀")))
+        );
+        assert_eq!(
+            call(
+                "indexOf",
+                &[string("☀ Tatooine"), string("Tatooine")],
+                &mut random
+            ),
+            Some(Ok(Node::Integer(2)))
+        );
+        assert_eq!(
+            call(
+                "indexOf",
+                &[string("Tatooine"), string("Hoth")],
+                &mut random
+            ),
+            Some(Ok(Node::Integer(-1)))
+        );
+        assert_eq!(
+            call("upper", &[string("straße")], &mut random),
+            Some(Ok(string("STRASSE")))
+        );
+        assert_eq!(
+            call("lower", &[string("R2-D2")], &mut random),
+            Some(Ok(string("r2-d2")))
+        );
+        assert_eq!(
+            call("trim", &[string("  Yoda ")], &mut random),
+            Some(Ok(string("Yoda")))
+        );
+        assert_eq!(
+            call("split", &[string("Han,Leia"), string(",")], &mut random),
+            Some(Ok(Node::List(vec![string("Han"), string("Leia")])))
+        );
+        assert_eq!(
+            call("split", &[string("BB8"), string("")], &mut random),
+            Some(Ok(Node::List(vec![string("B"), string("B"), string("8")])))
+        );
+        assert_eq!(
+            call(
+                "replace",
+                &[string("Death Star"), string("Death"), string("Dark")],
+                &mut random
+            ),
+            Some(Ok(string("Dark Star")))
+        );
+
+        assert_eq!(call("Wookiee", &[], &mut random), None);
+        assert!(matches!(
+            call(
+                "substring",
+                &[string("Hoth"), Node::Integer(2), Node::Integer(9)],
+                &mut random
+            ),
+            Some(Err(Error::Runtime(_)))
+        ));
+        assert!(matches!(
+            call("upper", &[Node::Integer(2)], &mut random),
+            Some(Err(Error::Type(_)))
+        ));
+    }
+
+    #[test]
+    fn math() {
+        let mut random = Random::new(0);
+        assert_eq!(
+            call("sqrt", &[Node::Integer(16)], &mut random),
+            Some(Ok(Node::Float(4.0)))
+        );
+        assert_eq!(
+            call("cos", &[Node::Float(0.0)], &mut random),
+            Some(Ok(Node::Float(1.0)))
+        );
+        assert_eq!(
+            call("floor", &[Node::Float(-1.5)], &mut random),
+            Some(Ok(Node::Float(-2.0)))
+        );
+        assert_eq!(
+            call("round", &[Node::Integer(3)], &mut random),
+            Some(Ok(Node::Integer(3)))
+        );
+        assert_eq!(
+            call("abs", &[Node::Integer(-3)], &mut random),
+            Some(Ok(Node::Integer(3)))
+        );
+        assert_eq!(
+            call("min", &[Node::Integer(2), Node::Integer(-5)], &mut random),
+            Some(Ok(Node::Integer(-5)))
+        );
+        assert_eq!(
+            call("max", &[Node::Integer(2), Node::Float(2.5)], &mut random),
+            Some(Ok(Node::Float(2.5)))
+        );
+
+        assert!(matches!(
+            call("sqrt", &[Node::Float(-1.0)], &mut random),
+            Some(Err(Error::Runtime(_)))
+        ));
+        assert!(matches!(
+            call("abs", &[Node::Integer(i64::MIN)], &mut random),
+            Some(Err(Error::Runtime(_)))
+        ));
+        assert!(matches!(
+            call("floor", &[string("1.5")], &mut random),
+            Some(Err(Error::Type(_)))
+        ));
+    }
+
+    #[test]
+    fn random() {
+        // The same seed always gives the same numbers
+        let mut first = Random::new(42);
+        let mut second = Random::new(42);
+        for _ in 0..100 {
+            let roll = call(
+                "randomInteger",
+                &[Node::Integer(1), Node::Integer(6)],
+                &mut first,
+            );
+            assert_eq!(
+                roll,
+                call(
+                    "randomInteger",
+                    &[Node::Integer(1), Node::Integer(6)],
+                    &mut second
+                )
+            );
+            assert!(matches!(roll, Some(Ok(Node::Integer(1..=6)))));
+
+            match call("random", &[], &mut first) {
+                Some(Ok(Node::Float(float))) => assert!((0.0..1.0).contains(&float)),
+                _ => panic!("Expected a float"),
+            }
+            call("random", &[], &mut second);
+        }
+
+        assert!(matches!(
+            call(
+                "randomInteger",
+                &[Node::Integer(i64::MIN), Node::Integer(i64::MAX)],
+                &mut first
+            ),
+            Some(Ok(Node::Integer(_)))
+        ));
+        assert!(matches!(
+            call(
+                "randomInteger",
+                &[Node::Integer(6), Node::Integer(1)],
+                &mut first
+            ),
+            Some(Err(Error::Runtime(_)))
+        ));
+    }
+}
@@ -0,0 +1,636 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    ast::{Node, Type},
+    error::Error,
+};
+
+/// What a name refers to, which decides if it can be changed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Binding {
+    Variable,
+    Constant,
+    /// Copied into an anonymous function from around it
+    Captured,
+}
+
+/// A declared variable, with its type when it is known before the program runs.
+#[derive(Debug, Clone, PartialEq)]
+struct Variable {
+    binding: Binding,
+    kind: Option<Type>,
+}
+
+impl Variable {
+    fn new(kind: Option<Type>) -> Variable {
+        Variable {
+            binding: Binding::Variable,
+            kind,
+        }
+    }
+}
+
+/// The variables of each block, with the innermost block last.
+type Scopes = Vec<HashMap<String, Variable>>;
+
+/// The parameters and return type of a declared function, with the types given for them.
+struct Signature {
+    parameters: Vec<(String, Option<Type>)>,
+    returns: Option<Type>,
+}
+
+/// The functions and record types declared in the program, which every function can use.
+#[derive(Default)]
+struct Declarations {
+    functions: HashMap<String, Signature>,
+    records: HashSet<String>,
+}
+
+/// Finds mistakes that can be caught before a program is run, such as breaking out of a loop when
+/// there isn't one, assigning to a constant or calling a function with the wrong arguments.
+pub fn check(ast: &[Node]) -> Result<(), Error> {
+    // Globals are declared before main runs, so every function sees all of them
+    let mut globals = HashMap::new();
+    let mut declarations = Declarations::default();
+    for node in ast {
+        if let Some((name, variable)) = declaration(node) {
+            globals.insert(name.to_string(), variable);
+        }
+        if let Node::DeclareFunction(name, parameters, _, _, returns) = node {
+            let signature = Signature {
+                parameters: parameters.iter().filter_map(parameter).collect(),
+                returns: returns.clone(),
+            };
+            declarations.functions.insert(name.clone(), signature);
+        }
+        if let Node::DeclareRecord(name, _) = node {
+            declarations.records.insert(name.clone());
+        }
+    }
+
+    for node in ast {
+        check_node(node, 0, &mut vec![globals.clone()], &declarations)?;
+    }
+    Ok(())
+}
+
+fn check_node(
+    node: &Node,
+    loop_depth: usize,
+    scopes: &mut Scopes,
+    declarations: &Declarations,
+) -> Result<(), Error> {
+    check_values(node, scopes, declarations)?;
+    match node {
+        Node::Break if loop_depth == 0 => Err(Error::Parse(
+            "Punch it! can only be used inside a loop".to_string(),
+        )),
+        Node::Continue if loop_depth == 0 => Err(Error::Parse(
+            "Stay on target. can only be used inside a loop".to_string(),
+        )),
+        // Loops do not reach into the functions they call, and only globals are shared with them
+        Node::DeclareFunction(_, parameters, statements, _, returns) => {
+            check_types(parameters, returns, declarations)?;
+            check_function(parameters, statements, scopes[0].clone(), declarations)
+        }
+        Node::Main(statements) => check_function(&[], statements, scopes[0].clone(), declarations),
+        Node::For(_, flag, statements) => {
+            check_assignment(flag, scopes)?;
+            check_block(statements, loop_depth + 1, None, scopes, declarations)
+        }
+        Node::ForEach(_, item, statements) => {
+            check_block(statements, loop_depth + 1, Some(item), scopes, declarations)
+        }
+        Node::While(_, statements) => {
+            check_block(statements, loop_depth + 1, None, scopes, declarations)
+        }
+        Node::If(_, if_statements, else_statements) => {
+            check_block(if_statements, loop_depth, None, scopes, declarations)?;
+            check_block(else_statements, loop_depth, None, scopes, declarations)
+        }
+        Node::Try(try_statements, variable, catch_statements) => {
+            check_block(try_statements, loop_depth, None, scopes, declarations)?;
+            let variable = variable.clone().map(Node::Variable);
+            check_block(
+                catch_statements,
+                loop_depth,
+                variable.as_ref(),
+                scopes,
+                declarations,
+            )
+        }
+        Node::AssignVariable(name, _, _) => check_assignment(&Node::Variable(name.clone()), scopes),
+        Node::ReadBoolean(variable) | Node::ReadFloat(variable) | Node::ReadString(variable) => {
+            check_assignment(variable, scopes)
+        }
+        _ => {
+            if let (Some((name, variable)), Some(scope)) = (declaration(node), scopes.last_mut()) {
+                scope.insert(name.to_string(), variable);
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Checks a function body, which starts outside of any loop with its parameters declared.
+fn check_function(
+    parameters: &[Node],
+    statements: &[Node],
+    outer: HashMap<String, Variable>,
+    declarations: &Declarations,
+) -> Result<(), Error> {
+    let parameters = parameters
+        .iter()
+        .filter_map(parameter)
+        .map(|(name, kind)| (name, Variable::new(kind)))
+        .collect();
+    check_statements(statements, 0, &mut vec![outer, parameters], declarations)
+}
+
+/// Types that aren't built in must name a declared record type.
+fn check_types(
+    parameters: &[Node],
+    returns: &Option<Type>,
+    declarations: &Declarations,
+) -> Result<(), Error> {
+    let types = parameters
+        .iter()
+        .filter_map(parameter)
+        .filter_map(|(_, kind)| kind)
+        .chain(returns.clone());
+    for kind in types {
+        match kind {
+            Type::Record(name) if !declarations.records.contains(&name) => {
+                return Err(Error::Type(format!("There is no type named {}", name)));
+            }
+            _ => (),
+        }
+    }
+    Ok(())
+}
+
+/// Checks what is written inside the values a statement uses. Anonymous functions are checked like
+/// any other function, seeing globals and what they capture from around them. Calls to declared
+/// functions are checked against their signatures.
+fn check_values(node: &Node, scopes: &Scopes, declarations: &Declarations) -> Result<(), Error> {
+    match node {
+        Node::AnonymousFunction(parameters, statements, _, returns) => {
+            check_types(parameters, returns, declarations)?;
+            let mut outer = HashMap::new();
+            for (index, scope) in scopes.iter().enumerate() {
+                for (name, variable) in scope {
+                    // Only globals are shared, everything else is a copy
+                    let mut variable = variable.clone();
+                    if index > 0 {
+                        variable.binding = Binding::Captured;
+                    }
+                    outer.insert(name.clone(), variable);
+                }
+            }
+            check_function(parameters, statements, outer, declarations)
+        }
+        Node::Expression(value, operations) | Node::AssignVariable(_, value, operations) => {
+            check_values(value, scopes, declarations)?;
+            for operation in operations {
+                check_values(operation, scopes, declarations)?;
+            }
+            Ok(())
+        }
+        Node::CallFunction(name, arguments) => {
+            for argument in arguments {
+                check_values(argument, scopes, declarations)?;
+            }
+            check_call(name, arguments, scopes, declarations)
+        }
+        Node::SetElement(_, index, value) => {
+            check_values(index, scopes, declarations)?;
+            check_values(value, scopes, declarations)
+        }
+        Node::Append(_, value)
+        | Node::Binary(_, value)
+        | Node::DeclareBoolean(_, value)
+        | Node::DeclareConstant(value)
+        | Node::DeclareFloat(_, value)
+        | Node::DeclareFunctionVariable(_, value)
+        | Node::DeclareInteger(_, value)
+        | Node::DeclareString(_, value)
+        | Node::Print(value)
+        | Node::PrintLine(value)
+        | Node::Return(value)
+        | Node::SetField(_, _, value) => check_values(value, scopes, declarations),
+        _ => Ok(()),
+    }
+}
+
+/// Arguments whose type is known are checked against the types the declared function gives its
+/// parameters. A variable holding a function hides the declared one, so isn't checked.
+fn check_call(
+    name: &str,
+    arguments: &[Node],
+    scopes: &Scopes,
+    declarations: &Declarations,
+) -> Result<(), Error> {
+    let signature = match declarations.functions.get(name) {
+        Some(signature) if lookup(name, scopes).is_none() => signature,
+        _ => return Ok(()),
+    };
+    if arguments.len() != signature.parameters.len() {
+        return Err(Error::Type(format!(
+            "{} takes {} arguments, not {}",
+            name,
+            signature.parameters.len(),
+            arguments.len()
+        )));
+    }
+
+    for (argument, (parameter, expected)) in arguments.iter().zip(&signature.parameters) {
+        let (expected, found) = match (expected, known_type(argument, scopes, declarations)) {
+            (Some(expected), Some(found)) => (expected, found),
+            _ => continue,
+        };
+        // Integers widen into floats
+        if found != *expected && !(*expected == Type::Float && found == Type::Integer) {
+            return Err(Error::Type(format!(
+                "{} expected {} to be of type {}, not {}",
+                name, parameter, expected, found
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// The type a value will have when the program runs, if it can be told without running it.
+fn known_type(node: &Node, scopes: &Scopes, declarations: &Declarations) -> Option<Type> {
+    match node {
+        Node::Variable(name) => lookup(name, scopes).and_then(|variable| variable.kind.clone()),
+        Node::CallFunction(name, _) if lookup(name, scopes).is_none() => declarations
+            .functions
+            .get(name)
+            .and_then(|signature| signature.returns.clone()),
+        Node::AnonymousFunction(_, _, _, _) => Some(Type::Function),
+        Node::Interpolation(_) => Some(Type::String),
+        _ => Type::of(node),
+    }
+}
+
+fn lookup<'a>(name: &str, scopes: &'a Scopes) -> Option<&'a Variable> {
+    scopes.iter().rev().find_map(|scope| scope.get(name))
+}
+
+fn check_statements(
+    statements: &[Node],
+    loop_depth: usize,
+    scopes: &mut Scopes,
+    declarations: &Declarations,
+) -> Result<(), Error> {
+    for statement in statements {
+        check_node(statement, loop_depth, scopes, declarations)?;
+    }
+    Ok(())
+}
+
+/// Checks statements in a block of their own, with the variable given declared in it first.
+fn check_block(
+    statements: &[Node],
+    loop_depth: usize,
+    variable: Option<&Node>,
+    scopes: &mut Scopes,
+    declarations: &Declarations,
+) -> Result<(), Error> {
+    let mut scope = HashMap::new();
+    if let Some(Node::Variable(name)) = variable {
+        scope.insert(name.clone(), Variable::new(None));
+    }
+    scopes.push(scope);
+    let result = check_statements(statements, loop_depth, scopes, declarations);
+    scopes.pop();
+    result
+}
+
+/// Constants and captured variables can't be changed. Assigning to a name nothing has declared
+/// yet declares it for the rest of the function, as it does when the program runs.
+fn check_assignment(variable: &Node, scopes: &mut Scopes) -> Result<(), Error> {
+    let name = match variable {
+        Node::Variable(name) => name,
+        _ => return Ok(()),
+    };
+    match lookup(name, scopes).map(|variable| variable.binding) {
+        Some(Binding::Constant) => Err(Error::Type(format!(
+            "{} is a constant, so it cannot be changed",
+            name
+        ))),
+        Some(Binding::Captured) => Err(Error::Type(format!(
+            "{} was captured by an anonymous function, so it cannot be changed",
+            name
+        ))),
+        Some(Binding::Variable) => Ok(()),
+        None => {
+            // The first scope is what the function sees from outside, the next is its own
+            if let Some(scope) = scopes.get_mut(1) {
+                scope.insert(name.clone(), Variable::new(None));
+            }
+            Ok(())
+        }
+    }
+}
+
+/// The name a statement declares, and what kind of variable it is.
+fn declaration(node: &Node) -> Option<(&str, Variable)> {
+    let (name, kind) = match node {
+        Node::DeclareConstant(declaration_node) => {
+            let (name, mut variable) = declaration(declaration_node)?;
+            variable.binding = Binding::Constant;
+            return Some((name, variable));
+        }
+        Node::DeclareBoolean(name, _) => (name, Type::Boolean),
+        Node::DeclareFloat(name, _) => (name, Type::Float),
+        Node::DeclareFunctionVariable(name, _) => (name, Type::Function),
+        Node::DeclareInteger(name, _) => (name, Type::Integer),
+        Node::DeclareString(name, _) => (name, Type::String),
+        Node::DeclareList(name) => (name, Type::List),
+        Node::DeclareMap(name) => (name, Type::Map),
+        Node::NewRecord(type_name, name) => (name, Type::Record(type_name.clone())),
+        _ => return None,
+    };
+    Some((name, Variable::new(Some(kind))))
+}
+
+/// The name of a function parameter, and its type if one is given.
+fn parameter(node: &Node) -> Option<(String, Option<Type>)> {
+    match node {
+        Node::Variable(name) => Some((name.clone(), None)),
+        Node::TypedParameter(name, kind) => Some((name.clone(), Some(kind.clone()))),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn loop_control() {
+        let ast = vec![Node::Main(vec![
+            Node::DeclareBoolean("flying".to_string(), Box::new(Node::Boolean(true))),
+            Node::While(
+                Box::new(Node::Variable("flying".to_string())),
+                vec![Node::If(
+                    Box::new(Node::Variable("flying".to_string())),
+                    vec![Node::Break],
+                    vec![Node::Continue],
+                )],
+            ),
+        ])];
+        assert_eq!(check(&ast), Ok(()));
+
+        let ast = vec![Node::Main(vec![Node::Break])];
+        assert!(matches!(check(&ast), Err(Error::Parse(_))));
+
+        // A loop in main does not cover the functions it calls
+        let ast = vec![
+            Node::DeclareFunction(
+                "Escape".to_string(),
+                vec![],
+                vec![Node::Continue],
+                true,
+                None,
+            ),
+            Node::Main(vec![
+                Node::DeclareFloat("tries".to_string(), Box::new(Node::Float(0.0))),
+                Node::For(
+                    Box::new(Node::Float(3.0)),
+                    Box::new(Node::Variable("tries".to_string())),
+                    vec![Node::CallFunction("Escape".to_string(), vec![])],
+                ),
+            ]),
+        ];
+        assert!(matches!(check(&ast), Err(Error::Parse(_))));
+    }
+
+    #[test]
+    fn constants() {
+        let ast = vec![
+            Node::DeclareConstant(Box::new(Node::DeclareInteger(
+                "limit".to_string(),
+                Box::new(Node::Integer(10)),
+            ))),
+            Node::DeclareFunction(
+                "Count".to_string(),
+                vec![Node::Variable("limit".to_string())],
+                vec![Node::AssignVariable(
+                    "limit".to_string(),
+                    Box::new(Node::Integer(1)),
+                    vec![],
+                )],
+                true,
+                None,
+            ),
+            Node::Main(vec![Node::While(
+                Box::new(Node::Boolean(true)),
+                vec![
+                    Node::DeclareInteger("limit".to_string(), Box::new(Node::Integer(0))),
+                    Node::ReadFloat(Box::new(Node::Variable("limit".to_string()))),
+                ],
+            )]),
+        ];
+        assert_eq!(check(&ast), Ok(()));
+
+        let ast = vec![
+            Node::DeclareConstant(Box::new(Node::DeclareInteger(
+                "limit".to_string(),
+                Box::new(Node::Integer(10)),
+            ))),
+            Node::DeclareFunction(
+                "Count".to_string(),
+                vec![],
+                vec![Node::AssignVariable(
+                    "limit".to_string(),
+                    Box::new(Node::Integer(1)),
+                    vec![],
+                )],
+                true,
+                None,
+            ),
+        ];
+        assert!(matches!(check(&ast), Err(Error::Type(_))));
+
+        let ast = vec![Node::Main(vec![
+            Node::DeclareConstant(Box::new(Node::DeclareString(
+                "name".to_string(),
+                Box::new(Node::String("Rey".to_string())),
+            ))),
+            Node::If(
+                Box::new(Node::Boolean(true)),
+                vec![Node::ReadString(Box::new(Node::Variable(
+                    "name".to_string(),
+                )))],
+                vec![],
+            ),
+        ])];
+        assert!(matches!(check(&ast), Err(Error::Type(_))));
+    }
+
+    #[test]
+    fn anonymous_functions() {
+        let ast = vec![Node::Main(vec![
+            Node::DeclareInteger("count".to_string(), Box::new(Node::Integer(1))),
+            Node::DeclareFunctionVariable(
+                "bump".to_string(),
+                Box::new(Node::AnonymousFunction(
+                    vec![Node::Variable("by".to_string())],
+                    vec![
+                        Node::DeclareInteger("total".to_string(), Box::new(Node::Integer(0))),
+                        Node::AssignVariable(
+                            "total".to_string(),
+                            Box::new(Node::Variable("count".to_string())),
+                            vec![],
+                        ),
+                        Node::AssignVariable("by".to_string(), Box::new(Node::Integer(2)), vec![]),
+                    ],
+                    true,
+                    None,
+                )),
+            ),
+        ])];
+        assert_eq!(check(&ast), Ok(()));
+
+        let ast = vec![Node::Main(vec![
+            Node::DeclareInteger("count".to_string(), Box::new(Node::Integer(1))),
+            Node::DeclareFunctionVariable(
+                "bump".to_string(),
+                Box::new(Node::AnonymousFunction(
+                    vec![],
+                    vec![Node::AssignVariable(
+                        "count".to_string(),
+                        Box::new(Node::Integer(2)),
+                        vec![],
+                    )],
+                    true,
+                    None,
+                )),
+            ),
+        ])];
+        assert!(matches!(check(&ast), Err(Error::Type(_))));
+
+        // A name first assigned in a block belongs to the whole function, so it is captured too
+        let ast = vec![Node::Main(vec![
+            Node::If(
+                Box::new(Node::Boolean(true)),
+                vec![Node::AssignVariable(
+                    "speed".to_string(),
+                    Box::new(Node::Float(1.5)),
+                    vec![],
+                )],
+                vec![],
+            ),
+            Node::DeclareFunctionVariable(
+                "boost".to_string(),
+                Box::new(Node::AnonymousFunction(
+                    vec![],
+                    vec![Node::AssignVariable(
+                        "speed".to_string(),
+                        Box::new(Node::Float(2.0)),
+                        vec![],
+                    )],
+                    true,
+                    None,
+                )),
+            ),
+        ])];
+        assert!(matches!(check(&ast), Err(Error::Type(_))));
+
+        // A loop around an anonymous function does not reach into it
+        let ast = vec![Node::Main(vec![Node::While(
+            Box::new(Node::Boolean(true)),
+            vec![Node::DeclareFunctionVariable(
+                "stop".to_string(),
+                Box::new(Node::AnonymousFunction(
+                    vec![],
+                    vec![Node::Break],
+                    true,
+                    None,
+                )),
+            )],
+        )])];
+        assert!(matches!(check(&ast), Err(Error::Parse(_))));
+    }
+
+    #[test]
+    fn typed_functions() {
+        let half = Node::DeclareFunction(
+            "Half".to_string(),
+            vec![Node::TypedParameter("x".to_string(), Type::Float)],
+            vec![Node::Return(Box::new(Node::Variable("x".to_string())))],
+            false,
+            Some(Type::Float),
+        );
+        let call = |argument: Node| Node::CallFunction("Half".to_string(), vec![argument]);
+
+        // Integers widen into floats and expressions are checked when they run
+        let ast = vec![
+            half.clone(),
+            Node::Main(vec![
+                Node::DeclareInteger("count".to_string(), Box::new(Node::Integer(3))),
+                call(Node::Integer(4)),
+                call(Node::Variable("count".to_string())),
+                call(Node::Expression(
+                    Box::new(Node::Variable("count".to_string())),
+                    vec![],
+                )),
+            ]),
+        ];
+        assert_eq!(check(&ast), Ok(()));
+
+        let ast = vec![
+            half.clone(),
+            Node::Main(vec![call(Node::String("four".to_string()))]),
+        ];
+        assert!(matches!(check(&ast), Err(Error::Type(_))));
+
+        let ast = vec![
+            half.clone(),
+            Node::Main(vec![
+                Node::DeclareString(
+                    "count".to_string(),
+                    Box::new(Node::String("three".to_string())),
+                ),
+                call(Node::Variable("count".to_string())),
+            ]),
+        ];
+        assert!(matches!(check(&ast), Err(Error::Type(_))));
+
+        let ast = vec![
+            half,
+            Node::Main(vec![Node::CallFunction("Half".to_string(), vec![])]),
+        ];
+        assert!(matches!(check(&ast), Err(Error::Type(_))));
+
+        // Types that aren't built in must be declared records
+        let ship = Node::DeclareRecord("Ship".to_string(), vec![]);
+        let typed = |kind: &str| {
+            Node::DeclareFunction(
+                "Fly".to_string(),
+                vec![Node::TypedParameter("ship".to_string(), Type::named(kind))],
+                vec![],
+                true,
+                None,
+            )
+        };
+        assert_eq!(check(&[ship.clone(), typed("Ship")]), Ok(()));
+        assert!(matches!(
+            check(&[ship, typed("flaot")]),
+            Err(Error::Type(_))
+        ));
+
+        let ast = vec![Node::Main(vec![Node::DeclareFunctionVariable(
+            "fly".to_string(),
+            Box::new(Node::AnonymousFunction(
+                vec![],
+                vec![Node::Return(Box::new(Node::Integer(1)))],
+                false,
+                Some(Type::named("Ship")),
+            )),
+        )])];
+        assert!(matches!(check(&ast), Err(Error::Type(_))));
+    }
+}
@@ -1,8 +1,10 @@
 extern crate clap;
-use clap::{App, Arg, ArgMatches};
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
 
 use std::fs;
 
+use theforce::{error::Error, interpreter::Options};
+
 pub fn parse_arguments() -> ArgMatches<'static> {
     App::new(env!("CARGO_PKG_NAME"))
         .version(env!("CARGO_PKG_VERSION"))
@@ -12,18 +14,52 @@ pub fn parse_arguments() -> ArgMatches<'static> {
             "For more information, please visit {}",
             env!("CARGO_PKG_HOMEPAGE")
         ))
+        .setting(AppSettings::SubcommandsNegateReqs)
+        .arg(
+            Arg::with_name("seed")
+                .help("Fixes the random numbers given to the program, so runs can be repeated.")
+                .long("seed")
+                .value_name("SEED")
+                .takes_value(true)
+                .global(true)
+                .validator(|seed| {
+                    seed.parse::<u64>()
+                        .map(|_| ())
+                        .map_err(|_| "The seed must be a whole number".to_string())
+                }),
+        )
         .arg(
             Arg::with_name("PATH")
                 .help("The path to a `.force` file to run.")
                 .required(true)
                 .index(1),
         )
+        .subcommand(
+            SubCommand::with_name("test")
+                .about(
+                    "Runs each `.force` file with an adjacent `.out` file and compares the output.",
+                )
+                .arg(
+                    Arg::with_name("PATH")
+                        .help("The path to a directory or `.force` file to test.")
+                        .required(true)
+                        .index(1),
+                ),
+        )
         .get_matches()
 }
 
-pub fn read_source(args: ArgMatches) -> Result<String, String> {
+pub fn read_options(args: &ArgMatches) -> Options {
+    Options {
+        // Already checked by the validator
+        seed: args.value_of("seed").map(|seed| seed.parse().unwrap()),
+        ..Options::default()
+    }
+}
+
+pub fn read_source(args: ArgMatches) -> Result<String, Error> {
     match fs::read_to_string(args.value_of("PATH").unwrap()) {
         Ok(content) => Ok(content),
-        Err(_) => Err("File could not be read".to_string()),
+        Err(_) => Err(Error::Io("File could not be read".to_string())),
     }
 }
@@ -0,0 +1,37 @@
+use std::fmt;
+
+/// Everything that can go wrong running a program. Each kind exits with its own status code, so
+/// scripts calling The Force can tell them apart.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+    Parse(String),
+    Type(String),
+    Runtime(String),
+    /// A failed assertion, which stops the program even inside a try block.
+    Assertion(String),
+    Io(String),
+}
+
+impl Error {
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::Runtime(_) | Error::Assertion(_) => 1,
+            Error::Parse(_) => 2,
+            Error::Type(_) => 3,
+            Error::Io(_) => 4,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        match &self {
+            Error::Parse(message) => write!(f, "Error encountered while parsing: {}", message),
+            Error::Type(message) => write!(f, "Type error: {}", message),
+            Error::Runtime(message) | Error::Assertion(message) => {
+                write!(f, "Runtime error: {}", message)
+            }
+            Error::Io(message) => write!(f, "I/O error: {}", message),
+        }
+    }
+}
@@ -1,34 +1,143 @@
 use std::{
-    collections::HashMap,
+    cmp::Ordering,
+    collections::{BTreeMap, HashMap, HashSet},
+    convert::TryFrom,
+    fmt,
     io::{BufRead, Write},
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
 };
 
-use crate::ast::{BinaryOperation, Node, UnaryOperation};
+use crate::ast::{BinaryOperation, Formatted, Node, NumberFormat, Type, UnaryOperation};
+use crate::builtins::{self, Random};
+use crate::error::Error;
 
 struct Frame {
-    variables: HashMap<String, Node>,
+    /// The variables of each block being run, with the innermost block last.
+    scopes: Vec<HashMap<String, Node>>,
+    /// The names declared as constants in each block, kept in step with the scopes.
+    constants: Vec<HashSet<String>>,
+    /// The variables an anonymous function captured when it was made. They can be read but not
+    /// assigned.
+    captured: HashMap<String, Node>,
     current: Node,
 }
 
 impl Frame {
     fn new() -> Frame {
         Frame {
-            variables: HashMap::new(),
+            scopes: vec![HashMap::new()],
+            constants: vec![HashSet::new()],
+            captured: HashMap::new(),
             current: Node::Noop,
         }
     }
+
+    fn push_scope(&mut self) {
+        self.scopes.push(HashMap::new());
+        self.constants.push(HashSet::new());
+    }
+
+    fn pop_scope(&mut self) {
+        self.scopes.pop();
+        self.constants.pop();
+    }
+
+    /// Every variable, with those in inner blocks replacing any of the same name in outer ones.
+    fn into_variables(self) -> HashMap<String, Node> {
+        self.scopes.into_iter().flatten().collect()
+    }
 }
 
 #[derive(Clone)]
 struct Function {
+    /// What errors call it by, the name a closure is called through for anonymous functions.
+    name: String,
     parameters: Vec<Node>,
     body: Vec<Node>,
     void: bool,
+    returns: Option<Type>,
+    captured: HashMap<String, Node>,
+}
+
+/// Statements that stop the rest of the program from running as normal.
+#[derive(Debug, Clone, PartialEq)]
+enum Control {
+    Exit(i32),
+    Return,
+    Break,
+    Continue,
+}
+
+type NativeFunction = dyn Fn(&[Node]) -> Result<Node, Error> + Send + Sync;
+
+/// A function written in Rust that programs can call like one of their own.
+#[derive(Clone)]
+struct Native {
+    parameters: Vec<Type>,
+    returns: Option<Type>,
+    function: Arc<NativeFunction>,
+}
+
+/// Functions written in Rust, given to programs by whatever is running them. Functions declared in
+/// a program come first, then these, then the built in ones.
+#[derive(Clone, Default)]
+pub struct Natives {
+    functions: HashMap<String, Native>,
+}
+
+impl Natives {
+    pub fn new() -> Natives {
+        Natives::default()
+    }
+
+    /// Adds a function taking arguments of the given types, replacing any with the same name. A
+    /// return type of None makes it void, so it should give back `Node::Noop`. Arguments are
+    /// checked before the function is called, with integers becoming floats where one is expected.
+    pub fn register<F>(
+        &mut self,
+        name: &str,
+        parameters: Vec<Type>,
+        returns: Option<Type>,
+        function: F,
+    ) where
+        F: Fn(&[Node]) -> Result<Node, Error> + Send + Sync + 'static,
+    {
+        let native = Native {
+            parameters,
+            returns,
+            function: Arc::new(function),
+        };
+        self.functions.insert(name.to_string(), native);
+    }
+}
+
+impl fmt::Debug for Natives {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        let mut names: Vec<_> = self.functions.keys().collect();
+        names.sort();
+        f.debug_set().entries(names).finish()
+    }
+}
+
+/// Settings for a run that come from outside the program.
+#[derive(Debug, Clone, Default)]
+pub struct Options {
+    /// Fixes the random numbers a program gets, so runs can be repeated.
+    pub seed: Option<u64>,
+    pub natives: Natives,
 }
 
 struct State<R, W> {
     functions: HashMap<String, Function>,
+    records: HashMap<String, Vec<Node>>,
     stack: Vec<Frame>,
+    globals: HashMap<String, Node>,
+    global_constants: HashSet<String>,
+    control: Option<Control>,
+    format: NumberFormat,
+    random: Random,
+    natives: Natives,
     reader: R,
     writer: W,
 }
@@ -38,267 +147,602 @@ where
     R: BufRead,
     W: Write,
 {
-    fn new(reader: R, writer: W) -> State<R, W> {
+    fn new(reader: R, writer: W, options: &Options) -> State<R, W> {
+        // Without a seed, every run is different
+        let seed = options.seed.unwrap_or_else(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|time| time.as_nanos() as u64)
+                .unwrap_or_default()
+        });
         State {
             functions: HashMap::new(),
+            records: HashMap::new(),
             stack: vec![Frame::new()],
+            globals: HashMap::new(),
+            global_constants: HashSet::new(),
+            control: None,
+            format: NumberFormat::Default,
+            random: Random::new(seed),
+            natives: options.natives.clone(),
             reader,
             writer,
         }
     }
 
-    fn get_current(&self) -> Result<&Node, String> {
+    fn get_current(&self) -> Result<&Node, Error> {
         match self.stack.last() {
             Some(frame) => Ok(&frame.current),
-            _ => Err("Current not found".to_string()),
+            _ => Err(Error::Runtime("Current not found".to_string())),
         }
     }
 
-    fn set_current(&mut self, new_current: Node) -> Result<(), String> {
+    fn set_current(&mut self, new_current: Node) -> Result<(), Error> {
         match self.stack.last_mut() {
             Some(frame) => {
                 frame.current = new_current;
                 Ok(())
             }
-            _ => Err("No last frame".to_string()),
+            _ => Err(Error::Runtime("No last frame".to_string())),
         }
     }
 
-    fn get_variable(&self, variable_name: &str) -> Result<&Node, String> {
+    /// Looks in the current function first, then what it captured, so both hide globals with the
+    /// same name.
+    fn get_variable(&self, variable_name: &str) -> Result<&Node, Error> {
         let variable_node = match self.stack.last() {
-            Some(frame) => frame.variables.get(variable_name),
+            Some(frame) => frame
+                .scopes
+                .iter()
+                .rev()
+                .find_map(|scope| scope.get(variable_name))
+                .or_else(|| frame.captured.get(variable_name)),
             None => None,
         };
 
-        match variable_node {
+        match variable_node.or_else(|| self.globals.get(variable_name)) {
             Some(variable) => Ok(variable),
-            None => Err("No variable found".to_string()),
+            None => Err(Error::Runtime("No variable found".to_string())),
         }
     }
 
-    /// Returns a Some of bool indicating if the variable is new (true) or an existing one (false).
-    /// Used to prevent variables from being re-declared.
-    fn set_variable(&mut self, variable_name: &str, variable_value: &Node) -> Result<bool, String> {
-        let variable_result = match self.stack.last_mut() {
-            Some(frame) => Some(
-                frame
-                    .variables
-                    .insert(variable_name.to_string(), variable_value.clone()),
-            ),
-            None => None,
+    /// Adds a variable to the innermost block, which hides any variable with the same name from
+    /// outer blocks or globals until the block ends.
+    fn declare_variable(
+        &mut self,
+        variable_name: &str,
+        variable_value: &Node,
+    ) -> Result<(), Error> {
+        let scope = match self
+            .stack
+            .last_mut()
+            .and_then(|frame| frame.scopes.last_mut())
+        {
+            Some(scope) => scope,
+            None => return Err(Error::Runtime("No last frame".to_string())),
         };
-        match variable_result {
-            Some(Some(last_value)) => {
-                // Verify the old value is the same type as the new one
-                if std::mem::discriminant(&last_value) == std::mem::discriminant(variable_value) {
-                    Ok(false)
-                } else {
-                    Err("Cannot change variable type".to_string())
-                }
+        if scope.contains_key(variable_name) {
+            return Err(Error::Runtime("Cannot redeclare a variable".to_string()));
+        }
+        scope.insert(variable_name.to_string(), variable_value.clone());
+        Ok(())
+    }
+
+    /// Stops the variable just declared in the innermost block from being assigned again.
+    fn make_constant(&mut self, variable_name: &str) -> Result<(), Error> {
+        match self
+            .stack
+            .last_mut()
+            .and_then(|frame| frame.constants.last_mut())
+        {
+            Some(constants) => {
+                constants.insert(variable_name.to_string());
+                Ok(())
+            }
+            None => Err(Error::Runtime("No last frame".to_string())),
+        }
+    }
+
+    /// Changes the variable the name refers to, or adds it to the outermost block of the function if
+    /// there isn't one, so it outlasts the block it was set in. The new value must be the same type
+    /// as the old one, and it can't be a constant.
+    fn set_variable(&mut self, variable_name: &str, variable_value: &Node) -> Result<(), Error> {
+        let frame = match self.stack.last_mut() {
+            Some(frame) => frame,
+            None => return Err(Error::Runtime("No last frame".to_string())),
+        };
+        let index = frame
+            .scopes
+            .iter()
+            .rposition(|scope| scope.contains_key(variable_name));
+        let constant = match index {
+            Some(index) => frame.constants[index].contains(variable_name),
+            None => self.global_constants.contains(variable_name),
+        };
+        if constant {
+            return Err(Error::Runtime(format!(
+                "Cannot assign to the constant {}",
+                variable_name
+            )));
+        }
+        if index.is_none() && frame.captured.contains_key(variable_name) {
+            return Err(Error::Runtime(format!(
+                "Cannot assign to the captured variable {}",
+                variable_name
+            )));
+        }
+
+        let variables = match (index, frame.scopes.first_mut()) {
+            (Some(index), _) => &mut frame.scopes[index],
+            (None, _) if self.globals.contains_key(variable_name) => &mut self.globals,
+            (None, Some(scope)) => scope,
+            (None, None) => return Err(Error::Runtime("No last frame".to_string())),
+        };
+
+        let variable_value = widen(variables.get(variable_name), variable_value);
+        if let Some(last_value) = variables.get(variable_name) {
+            // Verify the old value is the same type as the new one
+            if !same_type(last_value, &variable_value) {
+                return Err(Error::Type("Cannot change variable type".to_string()));
             }
-            // New value being set
-            Some(None) => Ok(true),
-            None => Err("No last frame".to_string()),
         }
+        variables.insert(variable_name.to_string(), variable_value);
+        Ok(())
     }
 }
 
-pub fn evaluate<R, W>(ast: Vec<Node>, reader: R, writer: W) -> Result<(), String>
+/// Runs the program, returning the status it exits with.
+pub fn evaluate<R, W>(ast: Vec<Node>, reader: R, writer: W, options: &Options) -> Result<i32, Error>
 where
     R: BufRead,
     W: Write,
 {
     let mut main = Node::Noop;
-    let state = &mut State::new(reader, writer);
+    let mut globals = Vec::new();
+    let state = &mut State::new(reader, writer, options);
 
     for node in ast {
         match &node {
             Node::Main(_) => {
                 main = node;
             }
-            Node::DeclareFunction(function_name, parameters, body, void) => {
+            Node::DeclareFunction(function_name, parameters, body, void, returns) => {
                 let function = Function {
+                    name: function_name.to_string(),
                     parameters: parameters.to_vec(),
                     body: body.to_vec(),
                     void: *void,
+                    returns: returns.clone(),
+                    captured: HashMap::new(),
                 };
                 state.functions.insert(function_name.to_string(), function);
             }
+            Node::DeclareRecord(type_name, fields) => {
+                state.records.insert(type_name.to_string(), fields.to_vec());
+            }
+            Node::DeclareBoolean(_, _)
+            | Node::DeclareConstant(_)
+            | Node::DeclareFloat(_, _)
+            | Node::DeclareFunctionVariable(_, _)
+            | Node::DeclareInteger(_, _)
+            | Node::DeclareString(_, _)
+            | Node::DeclareList(_)
+            | Node::DeclareMap(_)
+            | Node::NewRecord(_, _) => globals.push(node),
             _ => unreachable!(), // TODO: Get actual error message
         }
     }
 
-    evaluate_node(&main, state)
+    // Globals are declared in a frame of their own, then kept apart from every function's frame.
+    // Each is added as soon as it is declared, so functions called for later ones can use it
+    for global in &globals {
+        state.stack.push(Frame::new());
+        evaluate_node(global, state)?;
+        if let Some(frame) = state.stack.pop() {
+            state
+                .global_constants
+                .extend(frame.constants.iter().flatten().cloned());
+            for (name, value) in frame.into_variables() {
+                if state.globals.contains_key(&name) {
+                    return Err(Error::Runtime("Cannot redeclare a variable".to_string()));
+                }
+                state.globals.insert(name, value);
+            }
+        }
+        if state.control.is_some() {
+            break;
+        }
+    }
+
+    // Anonymous functions declared as globals use the other globals rather than copies of them
+    for value in state.globals.values_mut() {
+        if let Node::Closure(_, captured) = value {
+            captured.clear();
+        }
+    }
+
+    evaluate_node(&main, state)?;
+    state.writer.flush().map_err(|x| Error::Io(x.to_string()))?;
+
+    match state.control {
+        Some(Control::Exit(status)) => Ok(status),
+        _ => Ok(0),
+    }
 }
 
-fn evaluate_node<R, W>(ast: &Node, state: &mut State<R, W>) -> Result<(), String>
+fn evaluate_node<R, W>(ast: &Node, state: &mut State<R, W>) -> Result<(), Error>
 where
     R: BufRead,
     W: Write,
 {
     match ast {
+        // Copies every variable it can see, so later changes to them don't reach it
+        Node::AnonymousFunction(_, _, _, _) => {
+            let captured = match state.stack.last() {
+                Some(frame) => {
+                    let mut captured = frame.captured.clone();
+                    for scope in &frame.scopes {
+                        captured.extend(scope.clone());
+                    }
+                    captured
+                }
+                None => return Err(Error::Runtime("No last frame".to_string())),
+            };
+            state.set_current(Node::Closure(
+                Box::new(ast.clone()),
+                captured.into_iter().collect(),
+            ))
+        }
+        Node::Append(name, value) => {
+            evaluate_node(value, state)?;
+            let value = state.get_current()?.clone();
+
+            let mut items = match state.get_variable(name)? {
+                Node::List(items) => items.clone(),
+                _ => return Err(Error::Type("Variable is not a list".to_string())),
+            };
+            let value = widen(items.first(), &value);
+            check_element_type(items.first(), &value)?;
+            items.push(value);
+            state.set_variable(name, &Node::List(items))
+        }
         Node::AssignVariable(variable_name, initial_value, operations) => {
             // Validate the initial value produces a value
             match **initial_value {
                 Node::Float(_)
+                | Node::Integer(_)
                 | Node::Boolean(_)
                 | Node::String(_)
                 | Node::Variable(_)
-                | Node::CallFunction(_, _) => (),
-                _ => return Err("Initial does not produces a value".to_string()),
+                | Node::Expression(_, _)
+                | Node::Interpolation(_)
+                | Node::CallFunction(_, _)
+                | Node::Function(_)
+                | Node::AnonymousFunction(_, _, _, _) => (),
+                _ => return Err(Error::Type("Initial does not produces a value".to_string())),
             };
 
             // Place value at top of stack
-            evaluate_node(initial_value, state)?;
-            for operation in operations {
-                let _ = match operation {
-                    Node::Binary(operation, value) => evaluate_binary(operation, value, state),
-                    Node::Unary(operation) => evaluate_unary(operation, state),
-                    _ => Err("Invalid operation".to_string()),
-                };
+            if let Ok(Node::Float(_)) = state.get_variable(variable_name) {
+                let operations: Vec<Node> = operations.iter().map(float_literals).collect();
+                evaluate_node(&float_literals(initial_value), state)?;
+                evaluate_operations(&operations, state)?;
+            } else {
+                evaluate_node(initial_value, state)?;
+                evaluate_operations(operations, state)?;
             }
             let new_current = state.get_current()?.clone();
-            state.set_variable(variable_name, &new_current).map(|_| ())
+            state.set_variable(variable_name, &new_current)
+        }
+        Node::Assert(flag, source) => {
+            evaluate_node(flag, state)?;
+
+            // Only accept boolean results
+            match state.get_current()? {
+                Node::Boolean(true) => Ok(()),
+                Node::Boolean(false) => Err(Error::Assertion(format!(
+                    "I find your lack of faith disturbing. Assertion failed: {}",
+                    source
+                ))),
+                _ => Err(Error::Type("Not boolean.".to_string())),
+            }
         }
         // Taken care of by the assign variable
         Node::Binary(_, _) => unreachable!(),
         Node::Boolean(_) => state.set_current(ast.clone()),
+        Node::Break => {
+            state.control = Some(Control::Break);
+            Ok(())
+        }
+        Node::Closure(_, _) => state.set_current(ast.clone()),
         Node::CallFunction(name, arguments) => {
-            // Validate the function exists
-            let function = if let Some(function) = state.functions.get(name) {
-                function.clone()
-            } else {
-                return Err("Function not defined".to_string());
+            // A variable holding a function hides a function with the same name
+            let held = match state.get_variable(name) {
+                Ok(Node::Function(held)) => state.functions.get(held).cloned(),
+                Ok(Node::Closure(function, captured)) => match &**function {
+                    Node::AnonymousFunction(parameters, body, void, returns) => Some(Function {
+                        name: name.clone(),
+                        parameters: parameters.clone(),
+                        body: body.clone(),
+                        void: *void,
+                        returns: returns.clone(),
+                        captured: captured.clone().into_iter().collect(),
+                    }),
+                    _ => None,
+                },
+                _ => None,
             };
 
+            // Validate the function exists
+            // Functions in the program come first, so they can replace a built in one
+            let function =
+                if let Some(function) = held.or_else(|| state.functions.get(name).cloned()) {
+                    function
+                } else {
+                    let mut values = Vec::new();
+                    for argument in arguments {
+                        evaluate_node(argument, state)?;
+                        values.push(state.get_current()?.clone());
+                    }
+                    if let Some(native) = state.natives.functions.get(name) {
+                        return match call_native(name, native, &values)? {
+                            Node::Noop => Ok(()),
+                            value => state.set_current(value),
+                        };
+                    }
+                    return match builtins::call(name, &values, &mut state.random) {
+                        Some(result) => state.set_current(result?),
+                        None => Err(Error::Runtime("Function not defined".to_string())),
+                    };
+                };
+
             // // Validate the inputs match
             if arguments.len() != function.parameters.len() {
-                return Err("Parameters do not match arguments".to_string());
+                return Err(Error::Type("Parameters do not match arguments".to_string()));
             }
 
             // Create a new frame in the stack
             let mut new_frame = Frame::new();
+            new_frame.captured = function.captured.clone();
             for (argument, parameter) in arguments.iter().zip(function.parameters.iter()) {
                 // Processes argument
                 evaluate_node(argument, state)?;
 
                 // Validate that arguments and parameters are the right types
-                let parameter_name = if let Node::Variable(name) = parameter {
-                    name
-                } else {
-                    return Err("Parameter is not a string".to_string());
+                let (parameter_name, parameter_type) = match parameter {
+                    Node::Variable(name) => (name, None),
+                    Node::TypedParameter(name, parameter_type) => (name, Some(parameter_type)),
+                    _ => return Err(Error::Runtime("Parameter is not a string".to_string())),
                 };
 
-                if let Node::Float(_) | Node::Boolean(_) | Node::String(_) = state.get_current()? {
-                    new_frame
-                        .variables
-                        .insert(parameter_name.clone(), state.get_current()?.clone());
-                } else {
-                    return Err("Argument not a value".to_string());
+                let value = state.get_current()?;
+                if !is_value(value) {
+                    return Err(Error::Type("Argument not a value".to_string()));
                 }
+                let value = match parameter_type {
+                    Some(parameter_type) => {
+                        let value = widen_to(parameter_type, value);
+                        check_type(parameter_type, &value, || {
+                            format!(
+                                "{} expected {} to be of type",
+                                function.name, parameter_name
+                            )
+                        })?;
+                        value
+                    }
+                    None => value.clone(),
+                };
+                new_frame.scopes[0].insert(parameter_name.clone(), value);
             }
             state.stack.push(new_frame);
 
             // Evaluate the body
-            for statement in &function.body {
-                evaluate_node(statement, state)?;
-            }
+            evaluate_statements(&function.body, state)?;
 
-            // Pop the stack frame. If non-void, set the return value to the new current
+            // Pop the stack frame
             let possible_return = state.get_current()?.clone();
             state.stack.pop();
-            if !function.void {
-                state.set_current(possible_return)?;
+
+            // Exiting skips the rest of the program, so there is nothing to return
+            match state.control {
+                Some(Control::Exit(_)) => return Ok(()),
+                Some(Control::Return) => state.control = None,
+                Some(Control::Break) | Some(Control::Continue) => {
+                    return Err(Error::Runtime(
+                        "Loops cannot be left from inside a function".to_string(),
+                    ))
+                }
+                None => {
+                    if !function.void {
+                        return Err(Error::Type("Function did not return a value".to_string()));
+                    }
+                    return Ok(());
+                }
             }
 
-            Ok(())
-        }
-        Node::DeclareBoolean(name, boolean) => match **boolean {
-            Node::Boolean(value) => {
-                let result = state.set_variable(name, &Node::Boolean(value));
-                error_if_redeclare(result)
+            // A bare return leaves no value. If non-void, set the return value to the new current
+            match (function.void, possible_return) {
+                (true, Node::Noop) => Ok(()),
+                (true, _) => Err(Error::Type(
+                    "Void function cannot return a value".to_string(),
+                )),
+                (false, Node::Noop) => {
+                    Err(Error::Type("Function did not return a value".to_string()))
+                }
+                (false, value) => match &function.returns {
+                    Some(returns) => {
+                        let value = widen_to(returns, &value);
+                        check_type(returns, &value, || {
+                            format!("{} must return a value of type", function.name)
+                        })?;
+                        state.set_current(value)
+                    }
+                    None => state.set_current(value),
+                },
             }
-            _ => Err("Not boolean".to_string()),
+        }
+        Node::DeclareBoolean(name, boolean) => match evaluate_initial_value(boolean, state)? {
+            Some(value @ Node::Boolean(_)) => state.declare_variable(name, &value),
+            None => Ok(()),
+            _ => Err(Error::Type("Not boolean".to_string())),
         },
-        Node::DeclareFloat(name, float) => match **float {
-            Node::Float(value) => {
-                let result = state.set_variable(name, &Node::Float(value));
-                error_if_redeclare(result)
+        Node::DeclareConstant(declaration) => {
+            evaluate_node(declaration, state)?;
+            match declared_name(declaration) {
+                Some(name) => state.make_constant(name),
+                None => Err(Error::Runtime("Constant not a declaration".to_string())),
             }
-            _ => Err("Not float".to_string()),
+        }
+        Node::DeclareFloat(name, float) => {
+            match evaluate_initial_value(&float_literals(float), state)? {
+                Some(value @ Node::Float(_)) => state.declare_variable(name, &value),
+                // Integers widen into floats
+                Some(Node::Integer(value)) => {
+                    state.declare_variable(name, &Node::Float(value as f64))
+                }
+                None => Ok(()),
+                _ => Err(Error::Type("Not float".to_string())),
+            }
+        }
+        Node::DeclareFunctionVariable(name, function) => {
+            match evaluate_initial_value(function, state)? {
+                Some(value @ (Node::Function(_) | Node::Closure(_, _))) => {
+                    state.declare_variable(name, &value)
+                }
+                None => Ok(()),
+                _ => Err(Error::Type("Not function".to_string())),
+            }
+        }
+        Node::DeclareInteger(name, integer) => match evaluate_initial_value(integer, state)? {
+            Some(value @ Node::Integer(_)) => state.declare_variable(name, &value),
+            None => Ok(()),
+            _ => Err(Error::Type("Not integer".to_string())),
         },
         // Done in the evaluate function
-        Node::DeclareFunction(_, _, _, _) => unreachable!(),
-        Node::DeclareString(name, string) => match &**string {
-            Node::String(value) => {
-                let result = state.set_variable(name, &Node::String(value.clone()));
-                error_if_redeclare(result)
-            }
-            _ => Err("Not string".to_string()),
+        Node::DeclareFunction(_, _, _, _, _) => unreachable!(),
+        Node::DeclareList(name) => state.declare_variable(name, &Node::List(Vec::new())),
+        // Done in the evaluate function
+        Node::DeclareRecord(_, _) => unreachable!(),
+        Node::DeclareMap(name) => state.declare_variable(name, &Node::Map(BTreeMap::new())),
+        Node::DeclareString(name, string) => match evaluate_initial_value(string, state)? {
+            Some(value @ Node::String(_)) => state.declare_variable(name, &value),
+            None => Ok(()),
+            _ => Err(Error::Type("Not string".to_string())),
         },
+        Node::Continue => {
+            state.control = Some(Control::Continue);
+            Ok(())
+        }
+        Node::Exit(node) => {
+            evaluate_node(node, state)?;
+
+            // Only accept whole numbers
+            let status = match state.get_current()? {
+                Node::Integer(integer) => i32::try_from(*integer).ok(),
+                Node::Float(float) if float.fract() == 0.0 => Some(*float as i32),
+                _ => None,
+            };
+            let status = match status {
+                Some(status) => status,
+                None => return Err(Error::Type("Exit status not a whole number".to_string())),
+            };
+            state.control = Some(Control::Exit(status));
+            Ok(())
+        }
+        Node::Expression(value, operations) => {
+            evaluate_node(value, state)?;
+            evaluate_operations(operations, state)
+        }
         Node::Float(_) => state.set_current(ast.clone()),
+        // Only functions declared in the program can be used as values
+        Node::Function(name) => {
+            if !state.functions.contains_key(name) {
+                return Err(Error::Runtime(format!("Function {} not defined", name)));
+            }
+            state.set_current(ast.clone())
+        }
         Node::For(max, flag, statements) => {
             // Validate params
-            let max_value = match **max {
-                Node::Float(max) => max,
-                Node::Variable(ref max_var) => {
-                    evaluate_node(&state.get_variable(max_var)?.clone(), state)?;
-                    if let Node::Float(max) = state.get_current()?.clone() {
-                        max
-                    } else {
-                        return Err("For max variable not a float".to_string());
-                    }
-                }
-                _ => return Err("For max not a float".to_string()),
-            };
+            evaluate_node(max, state)?;
+            let max_value = state.get_current()?.clone();
+            if as_float(&max_value).is_none() {
+                return Err(Error::Type("For max not a number".to_string()));
+            }
 
             let flag_var_name = if let Node::Variable(ref var_name) = **flag {
                 var_name
             } else {
-                return Err("For flag not a variable".to_string());
+                return Err(Error::Type("For flag not a variable".to_string()));
             };
 
             // For evaluation check
-            let evaluate_loop_flag = |flag: &Node, max: f32| -> Result<bool, String> {
-                match flag {
-                    Node::Float(float) => Ok(!float.eq(&max)),
-                    _ => Err("Flag not a float".to_string()),
-                }
+            let evaluate_loop_flag = |flag: &Node, max: &Node| -> Result<bool, Error> {
+                Ok(compare_numbers(flag, max)? != Some(Ordering::Equal))
             };
 
             // Check if should loop
             evaluate_node(&state.get_variable(flag_var_name)?.clone(), state)?;
-            let mut continue_loop = evaluate_loop_flag(state.get_current()?, max_value)?;
+            let mut continue_loop = evaluate_loop_flag(state.get_current()?, &max_value)?;
 
             // Loop
             while continue_loop {
-                for statement in statements {
-                    evaluate_node(statement, state)?;
+                evaluate_block(statements, None, state)?;
+                if leave_loop(state) {
+                    break;
                 }
 
                 // Get the variable value
                 evaluate_node(&state.get_variable(flag_var_name)?.clone(), state)?;
 
-                let flag_value = if let Node::Float(value) = state.get_current()?.clone() {
-                    // Increment variable value
-                    Node::Float(value + 1.0)
-                } else {
-                    return Err("Flag not a float".to_string());
+                // Increment variable value
+                let flag_value = match state.get_current()? {
+                    Node::Integer(value) => Node::Integer(integer_result(value.checked_add(1))?),
+                    Node::Float(value) => Node::Float(value + 1.0),
+                    _ => return Err(Error::Type("Flag not a number".to_string())),
                 };
 
                 // Set the variable value
                 state.set_variable(flag_var_name, &flag_value)?;
 
                 // Check if should loop
-                continue_loop = evaluate_loop_flag(&flag_value, max_value)?;
+                continue_loop = evaluate_loop_flag(&flag_value, &max_value)?;
+            }
+            Ok(())
+        }
+        Node::ForEach(list, item, statements) => {
+            let item_var_name = if let Node::Variable(ref var_name) = **item {
+                var_name
+            } else {
+                return Err(Error::Type("For each item not a variable".to_string()));
+            };
+
+            // Loop over a copy, so changing the list in the loop does not change what is visited.
+            // Maps are looped over by their keys.
+            evaluate_node(list, state)?;
+            let items = match state.get_current()? {
+                Node::List(items) => items.clone(),
+                Node::Map(entries) => entries.keys().cloned().map(Node::String).collect(),
+                _ => return Err(Error::Type("Can only loop over a list or map".to_string())),
+            };
+
+            for value in items {
+                evaluate_block(statements, Some((item_var_name, value)), state)?;
+                if leave_loop(state) {
+                    break;
+                }
             }
             Ok(())
         }
         Node::If(flag, true_statements, false_statements) => {
             // Flag not a value
             match **flag {
-                Node::Float(_) | Node::Boolean(_) | Node::String(_) | Node::Variable(_) => (),
-                _ => return Err("Flag not a value".to_string()),
+                Node::Float(_)
+                | Node::Integer(_)
+                | Node::Boolean(_)
+                | Node::String(_)
+                | Node::Variable(_)
+                | Node::Expression(_, _) => (),
+                _ => return Err(Error::Type("Flag not a value".to_string())),
             };
 
             // Processes flag
@@ -308,7 +752,7 @@ where
             let if_flag = if let Node::Boolean(bool) = state.get_current()? {
                 bool
             } else {
-                return Err("Not boolean.".to_string());
+                return Err(Error::Type("Not boolean.".to_string()));
             };
 
             // Choose a branch. False branch may not exist, but should be empty from parser
@@ -318,75 +762,243 @@ where
                 false_statements
             };
 
-            for statement in statements {
-                evaluate_node(statement, state)?;
+            evaluate_block(statements, None, state)
+        }
+        Node::Integer(_) => state.set_current(ast.clone()),
+        Node::Try(try_statements, error_name, catch_statements) => {
+            // Frames from functions that failed part way through are left on the stack
+            let depth = state.stack.len();
+            match evaluate_block(try_statements, None, state) {
+                // Only runtime errors can be caught, since the rest are mistakes in the program
+                Err(Error::Runtime(message)) => {
+                    state.stack.truncate(depth);
+                    let error = error_name
+                        .as_ref()
+                        .map(|name| (name, Node::String(message)));
+                    evaluate_block(catch_statements, error, state)
+                }
+                result => result,
             }
-
-            Ok(())
         }
-        Node::Main(statements) => {
-            for statement in statements {
-                evaluate_node(statement, state)?;
+        Node::List(_) => state.set_current(ast.clone()),
+        Node::Record(_, _) => state.set_current(ast.clone()),
+        Node::Main(statements) => evaluate_statements(statements, state),
+        Node::Map(_) => state.set_current(ast.clone()),
+        Node::NewRecord(type_name, name) => {
+            let declarations = match state.records.get(type_name) {
+                Some(declarations) => declarations.clone(),
+                None => return Err(Error::Runtime("Record type not defined".to_string())),
+            };
+
+            // Fields start with the values they are declared with, worked out in their own frame
+            state.stack.push(Frame::new());
+            let result = evaluate_statements(&declarations, state);
+            let frame = state.stack.pop();
+            result?;
+            let mut variables = match frame {
+                Some(frame) => frame.into_variables(),
+                None => return Err(Error::Runtime("No last frame".to_string())),
+            };
+
+            let mut fields = Vec::new();
+            for declaration in &declarations {
+                if let Some(field) = declared_name(declaration) {
+                    if let Some(value) = variables.remove(field) {
+                        fields.push((field.to_string(), value));
+                    }
+                }
             }
-            Ok(())
+
+            state.declare_variable(name, &Node::Record(type_name.clone(), fields))
         }
-        Node::Print(node) => {
+        Node::Print(node) | Node::PrintLine(node) => {
             // Validate it's a value
             match **node {
-                Node::Float(_) | Node::Boolean(_) | Node::String(_) | Node::Variable(_) => (),
-                _ => return Err("Return not a value".to_string()),
+                Node::Float(_)
+                | Node::Integer(_)
+                | Node::Boolean(_)
+                | Node::String(_)
+                | Node::Function(_)
+                | Node::Variable(_)
+                | Node::Expression(_, _)
+                | Node::Interpolation(_) => (),
+                _ => return Err(Error::Type("Return not a value".to_string())),
             };
 
             // Get the value and print
             evaluate_node(node, state)?;
             let value = state.get_current()?.clone();
-            write!(state.writer, "{}", value).map_err(|x| x.to_string())
+            write!(state.writer, "{}", Formatted(&value, &state.format))
+                .map_err(|x| Error::Io(x.to_string()))?;
+            if let Node::PrintLine(_) = ast {
+                writeln!(state.writer).map_err(|x| Error::Io(x.to_string()))?;
+            }
+            Ok(())
+        }
+        Node::FormatDefault => {
+            state.format = NumberFormat::Default;
+            Ok(())
+        }
+        Node::FormatFixed(places) => {
+            evaluate_node(places, state)?;
+            match state.get_current()? {
+                Node::Integer(places) => match usize::try_from(*places) {
+                    Ok(places) => {
+                        state.format = NumberFormat::Fixed(places);
+                        Ok(())
+                    }
+                    Err(_) => Err(Error::Runtime(
+                        "Decimal places cannot be negative".to_string(),
+                    )),
+                },
+                _ => Err(Error::Type("Decimal places must be an integer".to_string())),
+            }
+        }
+        Node::FormatRoman => {
+            state.format = NumberFormat::Roman;
+            Ok(())
+        }
+        Node::FormatScientific => {
+            state.format = NumberFormat::Scientific;
+            Ok(())
+        }
+        Node::Remove(name, index) => {
+            evaluate_node(index, state)?;
+            let index = state.get_current()?.clone();
+
+            let collection = match state.get_variable(name)?.clone() {
+                Node::List(mut items) => {
+                    let index = list_index(&index)?;
+                    if index >= items.len() {
+                        return Err(Error::Runtime("List index out of range".to_string()));
+                    }
+                    items.remove(index);
+                    Node::List(items)
+                }
+                Node::Map(mut entries) => {
+                    if entries.remove(&map_key(&index)?).is_none() {
+                        return Err(Error::Runtime("Map key not found".to_string()));
+                    }
+                    Node::Map(entries)
+                }
+                _ => return Err(Error::Type("Variable is not a list or map".to_string())),
+            };
+            state.set_variable(name, &collection)
         }
         Node::Return(node) => {
-            // Put onto stack
-            evaluate_node(node, state)?;
+            if let Node::Noop = **node {
+                // Nothing to return
+                state.set_current(Node::Noop)?;
+            } else {
+                // Put onto stack
+                evaluate_node(node, state)?;
 
-            // Validate it's a value
-            match state.get_current()? {
-                Node::Float(_) | Node::Boolean(_) | Node::String(_) => Ok(()),
-                _ => Err("Return not a value".to_string()),
+                // Validate it's a value
+                if !is_value(state.get_current()?) {
+                    return Err(Error::Type("Return not a value".to_string()));
+                }
             }
+
+            // Unwind out of any loops until the function is left
+            state.control = Some(Control::Return);
+            Ok(())
         }
         Node::ReadBoolean(variable) => read_value(variable, Node::Boolean, state),
         Node::ReadFloat(variable) => read_value(variable, Node::Float, state),
         Node::ReadString(variable) => read_value(variable, Node::String, state),
+        Node::SetElement(name, index, value) => {
+            evaluate_node(index, state)?;
+            let index = state.get_current()?.clone();
+            evaluate_node(value, state)?;
+            let value = state.get_current()?.clone();
+
+            // Lists can only change existing elements, while maps add any new keys
+            let collection = match state.get_variable(name)?.clone() {
+                Node::List(mut items) => {
+                    let value = widen(items.first(), &value);
+                    check_element_type(items.first(), &value)?;
+                    match items.get_mut(list_index(&index)?) {
+                        Some(item) => *item = value,
+                        None => return Err(Error::Runtime("List index out of range".to_string())),
+                    }
+                    Node::List(items)
+                }
+                Node::Map(mut entries) => {
+                    let value = widen(entries.values().next(), &value);
+                    check_element_type(entries.values().next(), &value)?;
+                    entries.insert(map_key(&index)?, value);
+                    Node::Map(entries)
+                }
+                _ => return Err(Error::Type("Variable is not a list or map".to_string())),
+            };
+            state.set_variable(name, &collection)
+        }
+        Node::SetField(name, field, value) => {
+            evaluate_node(value, state)?;
+            let value = state.get_current()?.clone();
+
+            let (type_name, mut fields) = match state.get_variable(name)? {
+                Node::Record(type_name, fields) => (type_name.clone(), fields.clone()),
+                _ => return Err(Error::Type("Variable is not a record".to_string())),
+            };
+            match fields.iter_mut().find(|(name, _)| name == field) {
+                Some((_, old_value)) => {
+                    let value = widen(Some(old_value), &value);
+                    if !same_type(old_value, &value) {
+                        return Err(Error::Type(format!(
+                            "Cannot change type of field {}",
+                            field
+                        )));
+                    }
+                    *old_value = value;
+                }
+                None => return Err(Error::Type(format!("{} has no field {}", type_name, field))),
+            }
+            state.set_variable(name, &Node::Record(type_name, fields))
+        }
         Node::String(_) => state.set_current(ast.clone()),
+        // Numbers are filled in the same way they would be printed
+        Node::Interpolation(parts) => {
+            let mut string = "".to_string();
+            for part in parts {
+                match part {
+                    Node::Variable(name) => {
+                        let value = state.get_variable(name)?;
+                        string.push_str(&Formatted(value, &state.format).to_string());
+                    }
+                    _ => string.push_str(&part.to_string()),
+                }
+            }
+            state.set_current(Node::String(string))
+        }
+        // Bound when the function is called
+        Node::TypedParameter(_, _) => unreachable!(),
         // Taken care of by the assign variable
         Node::Unary(_) => unreachable!(),
         Node::Variable(name) => state.set_current(state.get_variable(name)?.clone()),
         Node::While(flag, statements) => {
-            // Validate params
-            let flag_var_name = if let Node::Variable(ref var_name) = **flag {
-                var_name
-            } else {
-                return Err("While flag not variable".to_string());
-            };
-
             // While evaluation check
-            let evaluate_loop_flag = |flag: &Node| -> Result<bool, String> {
+            let evaluate_loop_flag = |flag: &Node| -> Result<bool, Error> {
                 match flag {
                     Node::Boolean(boolean) => Ok(*boolean),
                     Node::Float(float) => Ok(*float != 0.0),
-                    _ => Err("Flag not a boolean or float".to_string()),
+                    Node::Integer(integer) => Ok(*integer != 0),
+                    _ => Err(Error::Type("Flag not a boolean or float".to_string())),
                 }
             };
 
-            // Get the variable value and validate it
-            evaluate_node(&state.get_variable(flag_var_name)?.clone(), state)?;
+            // Work out the flag and validate it
+            evaluate_node(flag, state)?;
             let mut continue_loop = evaluate_loop_flag(state.get_current()?)?;
 
             // Start looping
             while continue_loop {
-                for statement in statements {
-                    evaluate_node(statement, state)?;
+                evaluate_block(statements, None, state)?;
+                if leave_loop(state) {
+                    break;
                 }
 
-                evaluate_node(&state.get_variable(flag_var_name)?.clone(), state)?;
+                evaluate_node(flag, state)?;
                 continue_loop = evaluate_loop_flag(state.get_current()?)?;
             }
             Ok(())
@@ -395,11 +1007,126 @@ where
     }
 }
 
+/// Checks the arguments and result of a native function against the types it was registered with.
+/// Its parameters have no names, so errors give their position instead.
+fn call_native(name: &str, native: &Native, arguments: &[Node]) -> Result<Node, Error> {
+    if arguments.len() != native.parameters.len() {
+        return Err(Error::Type("Parameters do not match arguments".to_string()));
+    }
+
+    let mut values = Vec::new();
+    for (position, (argument, parameter)) in arguments.iter().zip(&native.parameters).enumerate() {
+        let argument = widen_to(parameter, argument);
+        check_type(parameter, &argument, || {
+            format!("{} expected argument {} to be of type", name, position + 1)
+        })?;
+        values.push(argument);
+    }
+
+    let result = (native.function)(&values)?;
+    match &native.returns {
+        None if result == Node::Noop => Ok(result),
+        None => Err(Error::Type(
+            "Void function cannot return a value".to_string(),
+        )),
+        Some(returns) => {
+            let result = widen_to(returns, &result);
+            check_type(returns, &result, || {
+                format!("{} must return a value of type", name)
+            })?;
+            Ok(result)
+        }
+    }
+}
+
+/// Evaluates each statement in turn, stopping early if one changes the flow of the program.
+fn evaluate_statements<R, W>(statements: &[Node], state: &mut State<R, W>) -> Result<(), Error>
+where
+    R: BufRead,
+    W: Write,
+{
+    for statement in statements {
+        evaluate_node(statement, state)?;
+        if state.control.is_some() {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// Runs statements in a block of their own, so variables declared in them are dropped when it ends.
+/// The variable given, such as the item of a for each loop, is declared in the block first.
+fn evaluate_block<R, W>(
+    statements: &[Node],
+    variable: Option<(&String, Node)>,
+    state: &mut State<R, W>,
+) -> Result<(), Error>
+where
+    R: BufRead,
+    W: Write,
+{
+    match state.stack.last_mut() {
+        Some(frame) => frame.push_scope(),
+        None => return Err(Error::Runtime("No last frame".to_string())),
+    }
+
+    let result = match variable {
+        Some((name, value)) => state.declare_variable(name, &value),
+        None => Ok(()),
+    }
+    .and_then(|_| evaluate_statements(statements, state));
+
+    if let Some(frame) = state.stack.last_mut() {
+        frame.pop_scope();
+    }
+    result
+}
+
+/// Works out the value a variable is declared with. Current is cleared first so a call that
+/// returns nothing doesn't leave an older value behind. There is no value if the program exits
+/// while it is worked out, so nothing is declared.
+fn evaluate_initial_value<R, W>(
+    value: &Node,
+    state: &mut State<R, W>,
+) -> Result<Option<Node>, Error>
+where
+    R: BufRead,
+    W: Write,
+{
+    state.set_current(Node::Noop)?;
+    evaluate_node(value, state)?;
+    if let Some(Control::Exit(_)) = state.control {
+        return Ok(None);
+    }
+    Ok(Some(state.get_current()?.clone()))
+}
+
+/// Handles a loop body that stopped early. Returns if the loop should stop, consuming a break or
+/// continue meant for it.
+fn leave_loop<R, W>(state: &mut State<R, W>) -> bool
+where
+    R: BufRead,
+    W: Write,
+{
+    match state.control {
+        Some(Control::Break) => {
+            state.control = None;
+            true
+        }
+        Some(Control::Continue) => {
+            state.control = None;
+            false
+        }
+        Some(_) => true,
+        None => false,
+    }
+}
+
 fn read_value<V, F, R, W>(
     variable: &Node,
     function: F,
     state: &mut State<R, W>,
-) -> Result<(), String>
+) -> Result<(), Error>
 where
     V: std::str::FromStr,
     R: BufRead,
@@ -410,127 +1137,248 @@ where
     let variable_name = if let Node::Variable(variable_name) = variable {
         variable_name.clone()
     } else {
-        return Err("Not a variable".to_string());
+        return Err(Error::Runtime("Not a variable".to_string()));
     };
 
     // Get input from user
     let mut input = String::new();
     if state.reader.read_line(&mut input).is_err() {
-        return Err("Unable to read input".to_string());
+        return Err(Error::Io("Unable to read input".to_string()));
     }
 
     // Clean the input and convert it
     let input = if let Ok(input) = input.trim().parse::<V>() {
         input
     } else {
-        return Err("Unable to convert input".to_string());
+        return Err(Error::Runtime("Unable to convert input".to_string()));
     };
 
-    state
-        .set_variable(variable_name.as_str(), &function(input))
-        .map(|_| ())
+    state.set_variable(variable_name.as_str(), &function(input))
 }
 
-fn evaluate_binary<R, W>(
-    op: &BinaryOperation,
-    value: &Node,
-    state: &mut State<R, W>,
-) -> Result<(), String>
+/// Applies each operation in turn to the current value.
+fn evaluate_operations<R, W>(operations: &[Node], state: &mut State<R, W>) -> Result<(), Error>
 where
     R: BufRead,
     W: Write,
 {
+    for operation in operations {
+        match operation {
+            Node::Binary(operation, value) => evaluate_binary(operation, value, state)?,
+            Node::Unary(operation) => evaluate_unary(operation, state)?,
+            _ => return Err(Error::Runtime("Invalid operation".to_string())),
+        };
+    }
+    Ok(())
+}
+
+fn evaluate_binary<R, W>(
+    op: &BinaryOperation,
+    value: &Node,
+    state: &mut State<R, W>,
+) -> Result<(), Error>
+where
+    R: BufRead,
+    W: Write,
+{
+    // Groups and variables are worked out first, without losing the value they are applied to
+    let value = &match value {
+        Node::Expression(_, _) | Node::Interpolation(_) => {
+            let current = state.get_current()?.clone();
+            evaluate_node(value, state)?;
+            let result = state.get_current()?.clone();
+            state.set_current(current)?;
+            result
+        }
+        Node::Variable(var_name) => state.get_variable(var_name)?.clone(),
+        _ => value.clone(),
+    };
+
     match op {
-        BinaryOperation::Add => math_operations(|x, y| x + y, value, state),
-        BinaryOperation::Subtract => math_operations(|x, y| x - y, value, state),
-        BinaryOperation::Multiply => math_operations(|x, y| x * y, value, state),
-        BinaryOperation::Divide => math_operations(|x, y| x / y, value, state),
-        BinaryOperation::Exponent => math_operations(|x, y| x.powf(y), value, state),
-        BinaryOperation::Modulus => math_operations(|x, y| x % y, value, state),
-        BinaryOperation::Equal => {
-            let mut equal_value = value.clone();
-            if let Node::Variable(var_name) = &value {
-                equal_value = state.get_variable(var_name)?.clone()
-            };
-            match equal_value {
-                Node::Boolean(_) => equality_bool_operations(|x, y| x == y, value, state),
-                Node::Float(_) => equality_float_operations(|x, y| x.eq(&y), value, state),
-                Node::String(_) => equality_string_operations(|x, y| x.eq(y), value, state),
-                _ => unreachable!(),
+        BinaryOperation::Add => match (state.get_current()?, value) {
+            (Node::String(x), Node::String(y)) => {
+                let joined = format!("{}{}", x, y);
+                state.set_current(Node::String(joined))
+            }
+            _ => math_operations(
+                |x, y| integer_result(x.checked_add(y)),
+                |x, y| x + y,
+                value,
+                state,
+            ),
+        },
+        BinaryOperation::Subtract => math_operations(
+            |x, y| integer_result(x.checked_sub(y)),
+            |x, y| x - y,
+            value,
+            state,
+        ),
+        BinaryOperation::Multiply => math_operations(
+            |x, y| integer_result(x.checked_mul(y)),
+            |x, y| x * y,
+            value,
+            state,
+        ),
+        BinaryOperation::Divide => math_operations(
+            |x, y| {
+                if y == 0 {
+                    return Err(Error::Runtime("Cannot divide by zero".to_string()));
+                }
+                integer_result(x.checked_div(y))
+            },
+            |x, y| x / y,
+            value,
+            state,
+        ),
+        BinaryOperation::Exponent => math_operations(
+            |x, y| match u32::try_from(y) {
+                Ok(y) => integer_result(x.checked_pow(y)),
+                Err(_) => Err(Error::Runtime(
+                    "Integer exponents cannot be negative".to_string(),
+                )),
+            },
+            |x, y| x.powf(y),
+            value,
+            state,
+        ),
+        BinaryOperation::Modulus => math_operations(
+            |x, y| {
+                if y == 0 {
+                    return Err(Error::Runtime("Cannot divide by zero".to_string()));
+                }
+                integer_result(x.checked_rem(y))
+            },
+            |x, y| x % y,
+            value,
+            state,
+        ),
+        BinaryOperation::Equal => match value {
+            Node::Boolean(_) => equality_bool_operations(|x, y| x == y, value, state),
+            Node::Float(_) | Node::Integer(_) => {
+                comparison_operations(|order| order == Ordering::Equal, value, state)
+            }
+            Node::String(_) => equality_string_operations(|x, y| x.eq(y), value, state),
+            Node::List(_) | Node::Map(_) | Node::Record(_, _) => {
+                let current = state.get_current()?;
+                if !same_type(current, value) {
+                    return Err(Error::Type("Values are not the same type".to_string()));
+                }
+                let new_current = Node::Boolean(current == value);
+                state.set_current(new_current)
             }
+            _ => Err(Error::Type("Value cannot be compared".to_string())),
+        },
+        BinaryOperation::GreaterThan => {
+            comparison_operations(|order| order == Ordering::Greater, value, state)
+        }
+        BinaryOperation::LessThan => {
+            comparison_operations(|order| order == Ordering::Less, value, state)
         }
-        BinaryOperation::GreaterThan => equality_float_operations(|x, y| x > y, value, state),
-        BinaryOperation::LessThan => equality_float_operations(|x, y| x < y, value, state),
         BinaryOperation::Or => equality_bool_operations(|x, y| x || y, value, state),
         BinaryOperation::And => equality_bool_operations(|x, y| x && y, value, state),
+        BinaryOperation::Index => index_operation(value, state),
+        BinaryOperation::Field => {
+            let field = match value {
+                Node::String(field) => field,
+                _ => return Err(Error::Runtime("Field name not a string".to_string())),
+            };
+            let field_value = match state.get_current()? {
+                Node::Record(type_name, fields) => match fields
+                    .iter()
+                    .find(|(name, _)| name == field)
+                {
+                    Some((_, field_value)) => field_value.clone(),
+                    None => {
+                        return Err(Error::Type(format!("{} has no field {}", type_name, field)))
+                    }
+                },
+                _ => return Err(Error::Type("Only records have fields".to_string())),
+            };
+            state.set_current(field_value)
+        }
+        BinaryOperation::ContainsKey => {
+            let key = map_key(value)?;
+            let contains = match state.get_current()? {
+                Node::Map(entries) => entries.contains_key(&key),
+                _ => return Err(Error::Type("Only maps have keys".to_string())),
+            };
+            state.set_current(Node::Boolean(contains))
+        }
     }
 }
 
-fn math_operations<R, W, F>(
-    math_operation: F,
+fn index_operation<R, W>(index: &Node, state: &mut State<R, W>) -> Result<(), Error>
+where
+    R: BufRead,
+    W: Write,
+{
+    let item = match state.get_current()? {
+        Node::List(items) => match items.get(list_index(index)?) {
+            Some(item) => item.clone(),
+            None => return Err(Error::Runtime("List index out of range".to_string())),
+        },
+        Node::Map(entries) => match entries.get(&map_key(index)?) {
+            Some(item) => item.clone(),
+            None => return Err(Error::Runtime("Map key not found".to_string())),
+        },
+        _ => {
+            return Err(Error::Type(
+                "Only lists and maps can be indexed".to_string(),
+            ))
+        }
+    };
+    state.set_current(item)
+}
+
+/// Integers stay integers, but any float in the operation makes the result a float.
+fn math_operations<R, W, I, F>(
+    integer_operation: I,
+    float_operation: F,
     value: &Node,
     state: &mut State<R, W>,
-) -> Result<(), String>
+) -> Result<(), Error>
 where
     R: BufRead,
     W: Write,
-    F: Fn(f32, f32) -> f32,
+    I: Fn(i64, i64) -> Result<i64, Error>,
+    F: Fn(f64, f64) -> f64,
 {
-    match (&state.get_current()?, value) {
-        (Node::Float(float_x), Node::Float(float_y)) => {
-            let new_current = Node::Float(math_operation(*float_x, *float_y));
-            state.set_current(new_current)?;
-            Ok(())
+    let new_current = match (state.get_current()?, value) {
+        (Node::Integer(integer_x), Node::Integer(integer_y)) => {
+            Node::Integer(integer_operation(*integer_x, *integer_y)?)
         }
-        (Node::Float(float_x), Node::Variable(var_name)) => {
-            let var_value = state.get_variable(var_name)?;
-            if let Node::Float(float_y) = var_value {
-                let new_current = Node::Float(math_operation(*float_x, *float_y));
-                state.set_current(new_current)?;
-                Ok(())
-            } else {
-                Err("Variable is not float".to_string())
+        (x, y) => match (as_float(x), as_float(y)) {
+            (Some(float_x), Some(float_y)) => Node::Float(float_operation(float_x, float_y)),
+            _ => {
+                return Err(Error::Type(
+                    "Math operations only work on numbers".to_string(),
+                ))
             }
-        }
-        _ => unreachable!(),
-    }
+        },
+    };
+    state.set_current(new_current)
 }
 
-fn equality_float_operations<R, W, F>(
-    equality_operation: F,
+fn comparison_operations<R, W, F>(
+    comparison: F,
     value: &Node,
     state: &mut State<R, W>,
-) -> Result<(), String>
+) -> Result<(), Error>
 where
     R: BufRead,
     W: Write,
-    F: Fn(f32, f32) -> bool,
+    F: Fn(Ordering) -> bool,
 {
-    match (&state.get_current()?, value) {
-        (Node::Float(float_x), Node::Float(float_y)) => {
-            let new_current = Node::Boolean(equality_operation(*float_x, *float_y));
-            state.set_current(new_current)?;
-            Ok(())
-        }
-        (Node::Float(float_x), Node::Variable(var_name)) => {
-            let var_value = state.get_variable(var_name)?;
-            if let Node::Float(float_y) = var_value {
-                let new_current = Node::Boolean(equality_operation(*float_x, *float_y));
-                state.set_current(new_current)?;
-                Ok(())
-            } else {
-                Err("Variable is not float".to_string())
-            }
-        }
-        _ => unreachable!(),
-    }
+    // Nothing is equal to, greater than, or less than a float that is not a number
+    let order = compare_numbers(state.get_current()?, value)?;
+    state.set_current(Node::Boolean(order.is_some_and(comparison)))
 }
 
 fn equality_bool_operations<R, W, F>(
     bool_operation: F,
     value: &Node,
     state: &mut State<R, W>,
-) -> Result<(), String>
+) -> Result<(), Error>
 where
     R: BufRead,
     W: Write,
@@ -542,17 +1390,9 @@ where
             state.set_current(new_current)?;
             Ok(())
         }
-        (Node::Boolean(bool_x), Node::Variable(var_name)) => {
-            let var_value = state.get_variable(var_name)?;
-            if let Node::Boolean(bool_y) = var_value {
-                let new_current = Node::Boolean(bool_operation(*bool_x, *bool_y));
-                state.set_current(new_current)?;
-                Ok(())
-            } else {
-                Err("Variable is not boolean".to_string())
-            }
-        }
-        _ => unreachable!(),
+        _ => Err(Error::Type(
+            "Logic operations only work on booleans".to_string(),
+        )),
     }
 }
 
@@ -560,7 +1400,7 @@ fn equality_string_operations<R, W, F>(
     bool_operation: F,
     value: &Node,
     state: &mut State<R, W>,
-) -> Result<(), String>
+) -> Result<(), Error>
 where
     R: BufRead,
     W: Write,
@@ -572,21 +1412,11 @@ where
             state.set_current(new_current)?;
             Ok(())
         }
-        (Node::String(string_x), Node::Variable(var_name)) => {
-            let var_value = state.get_variable(var_name)?;
-            if let Node::String(string_y) = var_value {
-                let new_current = Node::Boolean(bool_operation(string_x, string_y));
-                state.set_current(new_current)?;
-                Ok(())
-            } else {
-                Err("Variable is not string".to_string())
-            }
-        }
-        _ => unreachable!(),
+        _ => Err(Error::Type("Value is not string".to_string())),
     }
 }
 
-fn evaluate_unary<R, W>(op: &UnaryOperation, state: &mut State<R, W>) -> Result<(), String>
+fn evaluate_unary<R, W>(op: &UnaryOperation, state: &mut State<R, W>) -> Result<(), Error>
 where
     R: BufRead,
     W: Write,
@@ -598,18 +1428,219 @@ where
                     let new_current = Node::Boolean(!bool);
                     state.set_current(new_current)?;
                 }
-                _ => unreachable!(),
+                _ => return Err(Error::Type("Not only works on booleans".to_string())),
             }
             Ok(())
         }
+        UnaryOperation::ToInteger => {
+            // Floats are rounded towards zero
+            let integer = match state.get_current()? {
+                Node::Integer(integer) => *integer,
+                Node::Float(float) if float.is_finite() => *float as i64,
+                Node::Boolean(boolean) => *boolean as i64,
+                Node::String(string) => match string.trim().parse::<i64>() {
+                    Ok(integer) => integer,
+                    Err(_) => return Err(conversion_error(string, "an integer")),
+                },
+                Node::Float(_) => {
+                    return Err(Error::Runtime(
+                        "Cannot convert an infinite or NaN float to an integer".to_string(),
+                    ))
+                }
+                _ => return Err(Error::Type("Cannot convert to an integer".to_string())),
+            };
+            state.set_current(Node::Integer(integer))
+        }
+        UnaryOperation::ToFloat => {
+            let float = match state.get_current()? {
+                Node::Boolean(boolean) => *boolean as i64 as f64,
+                Node::String(string) => match string.trim().parse::<f64>() {
+                    Ok(float) => float,
+                    Err(_) => return Err(conversion_error(string, "a float")),
+                },
+                value => match as_float(value) {
+                    Some(float) => float,
+                    None => return Err(Error::Type("Cannot convert to a float".to_string())),
+                },
+            };
+            state.set_current(Node::Float(float))
+        }
+        // Written the same way it would be printed
+        UnaryOperation::ToString => {
+            let string = Formatted(state.get_current()?, &state.format).to_string();
+            state.set_current(Node::String(string))
+        }
+        UnaryOperation::ToBoolean => {
+            let boolean = match state.get_current()? {
+                Node::Boolean(boolean) => *boolean,
+                Node::Integer(integer) => *integer != 0,
+                Node::Float(float) => *float != 0.0 && !float.is_nan(),
+                Node::String(string) => match string.trim() {
+                    "From a certain point of view." => true,
+                    "That's impossible!" => false,
+                    _ => return Err(conversion_error(string, "a boolean")),
+                },
+                _ => return Err(Error::Type("Cannot convert to a boolean".to_string())),
+            };
+            state.set_current(Node::Boolean(boolean))
+        }
+        UnaryOperation::Length => {
+            let length = match state.get_current()? {
+                Node::List(items) => items.len(),
+                Node::Map(entries) => entries.len(),
+                Node::String(string) => string.chars().count(),
+                _ => {
+                    return Err(Error::Type(
+                        "Length only works on strings, lists and maps".to_string(),
+                    ))
+                }
+            };
+            state.set_current(Node::Integer(length as i64))
+        }
+    }
+}
+
+fn conversion_error(string: &str, target: &str) -> Error {
+    Error::Runtime(format!("Cannot convert \"{}\" to {}", string, target))
+}
+
+/// Values that can be stored in a variable.
+fn is_value(node: &Node) -> bool {
+    matches!(
+        node,
+        Node::Float(_)
+            | Node::Integer(_)
+            | Node::Boolean(_)
+            | Node::String(_)
+            | Node::List(_)
+            | Node::Map(_)
+            | Node::Record(_, _)
+            | Node::Function(_)
+            | Node::Closure(_, _)
+    )
+}
+
+/// Records are only the same type when they share a name, and named and anonymous functions are
+/// both functions. For everything else the kind of value is enough.
+fn same_type(x: &Node, y: &Node) -> bool {
+    match (Type::of(x), Type::of(y)) {
+        (Some(x_type), Some(y_type)) => x_type == y_type,
+        _ => std::mem::discriminant(x) == std::mem::discriminant(y),
+    }
+}
+
+/// Integers can be used anywhere a float is expected, becoming a float.
+fn widen(target: Option<&Node>, value: &Node) -> Node {
+    match (target, value) {
+        (Some(Node::Float(_)), Node::Integer(integer)) => Node::Float(*integer as f64),
+        _ => value.clone(),
+    }
+}
+
+/// Errors with what was expected and what was found if the value isn't the type given. The
+/// message starts with what is being checked.
+fn check_type<F>(expected: &Type, value: &Node, subject: F) -> Result<(), Error>
+where
+    F: FnOnce() -> String,
+{
+    match Type::of(value) {
+        Some(found) if found == *expected => Ok(()),
+        Some(found) => Err(Error::Type(format!(
+            "{} {}, not {}",
+            subject(),
+            expected,
+            found
+        ))),
+        None => Err(Error::Type(format!("{} {}", subject(), expected))),
+    }
+}
+
+fn widen_to(target: &Type, value: &Node) -> Node {
+    match (target, value) {
+        (Type::Float, Node::Integer(integer)) => Node::Float(*integer as f64),
+        _ => value.clone(),
+    }
+}
+
+/// Reads the whole numbers written in a value given to a float variable as floats, so math on them
+/// works as it did before there were integers. Indexes are left alone, as they must be integers.
+fn float_literals(node: &Node) -> Node {
+    match node {
+        Node::Integer(integer) => Node::Float(*integer as f64),
+        Node::Expression(value, operations) => Node::Expression(
+            Box::new(float_literals(value)),
+            operations.iter().map(float_literals).collect(),
+        ),
+        Node::Binary(BinaryOperation::Index, _) => node.clone(),
+        Node::Binary(operation, value) => {
+            Node::Binary(operation.clone(), Box::new(float_literals(value)))
+        }
+        _ => node.clone(),
+    }
+}
+
+fn as_float(node: &Node) -> Option<f64> {
+    match node {
+        Node::Float(float) => Some(*float),
+        Node::Integer(integer) => Some(*integer as f64),
+        _ => None,
+    }
+}
+
+/// Compares two numbers, which is nothing for floats that are not a number.
+fn compare_numbers(x: &Node, y: &Node) -> Result<Option<Ordering>, Error> {
+    match (x, y) {
+        (Node::Integer(integer_x), Node::Integer(integer_y)) => Ok(Some(integer_x.cmp(integer_y))),
+        _ => match (as_float(x), as_float(y)) {
+            (Some(float_x), Some(float_y)) => Ok(float_x.partial_cmp(&float_y)),
+            _ => Err(Error::Type("Comparisons only work on numbers".to_string())),
+        },
+    }
+}
+
+fn integer_result(result: Option<i64>) -> Result<i64, Error> {
+    result.ok_or_else(|| Error::Runtime("Integer overflow".to_string()))
+}
+
+/// The name of the variable a statement declares, if it declares one.
+fn declared_name(node: &Node) -> Option<&str> {
+    match node {
+        Node::DeclareBoolean(name, _)
+        | Node::DeclareFloat(name, _)
+        | Node::DeclareFunctionVariable(name, _)
+        | Node::DeclareInteger(name, _)
+        | Node::DeclareString(name, _)
+        | Node::DeclareList(name)
+        | Node::DeclareMap(name) => Some(name),
+        _ => None,
+    }
+}
+
+fn list_index(index: &Node) -> Result<usize, Error> {
+    match index {
+        Node::Integer(integer) if *integer >= 0 => Ok(*integer as usize),
+        Node::Float(float) if float.fract() == 0.0 && *float >= 0.0 => Ok(*float as usize),
+        _ => Err(Error::Type("List index not a whole number".to_string())),
+    }
+}
+
+fn map_key(key: &Node) -> Result<String, Error> {
+    match key {
+        Node::String(key) => Ok(key.clone()),
+        _ => Err(Error::Type("Map key not a string".to_string())),
     }
 }
 
-fn error_if_redeclare(set_variable_result: Result<bool, String>) -> Result<(), String> {
-    match set_variable_result {
-        Ok(false) => Err("Cannot redeclare a variable".to_string()),
-        Ok(true) => Ok(()),
-        Err(error) => Err(error),
+/// Lists and maps only hold one type of value, decided by the first one added.
+fn check_element_type(first: Option<&Node>, value: &Node) -> Result<(), Error> {
+    if !is_value(value) {
+        return Err(Error::Type("Element not a value".to_string()));
+    }
+    match first {
+        Some(first) if !same_type(first, value) => {
+            Err(Error::Type("Cannot change element type".to_string()))
+        }
+        _ => Ok(()),
     }
 }
 
@@ -627,7 +1658,7 @@ mod tests {
             "Hello there".to_string(),
         )))])];
 
-        let result = evaluate(ast, input.lock(), &mut output);
+        let result = evaluate(ast, input.lock(), &mut output, &Options::default());
         assert!(result.is_ok());
 
         let output = String::from_utf8(output).expect("Not UTF-8");
@@ -643,7 +1674,7 @@ mod tests {
             Node::Print(Box::new(Node::Variable("jawa".to_string()))),
         ])];
 
-        let result = evaluate(ast, input.lock(), &mut output);
+        let result = evaluate(ast, input.lock(), &mut output, &Options::default());
         assert!(result.is_ok());
 
         let output = String::from_utf8(output).expect("Not UTF-8");
@@ -659,7 +1690,7 @@ mod tests {
             Node::Print(Box::new(Node::Variable("ewok".to_string()))),
         ])];
 
-        let result = evaluate(ast, input.lock(), &mut output);
+        let result = evaluate(ast, input.lock(), &mut output, &Options::default());
         assert!(result.is_ok());
 
         let output = String::from_utf8(output).expect("Not UTF-8");
@@ -672,7 +1703,7 @@ mod tests {
             Node::Print(Box::new(Node::Variable("darkSide".to_string()))),
         ])];
 
-        let result = evaluate(ast, input.lock(), &mut output);
+        let result = evaluate(ast, input.lock(), &mut output, &Options::default());
         assert!(result.is_ok());
 
         let output = String::from_utf8(output).expect("Not UTF-8");
@@ -700,7 +1731,7 @@ mod tests {
             Node::Print(Box::new(Node::Variable("porg".to_string()))),
         ])];
 
-        let result = evaluate(ast, input.lock(), &mut output);
+        let result = evaluate(ast, input.lock(), &mut output, &Options::default());
         assert!(result.is_ok());
 
         let output = String::from_utf8(output).expect("Not UTF-8");
@@ -708,251 +1739,2005 @@ mod tests {
     }
 
     #[test]
-    fn equality() {
-        let input = io::stdin();
-        let mut output = Vec::new();
-        let ast = vec![Node::Main(vec![
-            Node::DeclareFloat("anakin".to_string(), Box::new(Node::Float(27700.0))),
-            Node::DeclareFloat("luke".to_string(), Box::new(Node::Float(14500.0))),
-            Node::DeclareFloat("leia".to_string(), Box::new(Node::Float(14500.0))),
-            Node::DeclareBoolean("midichlorian".to_string(), Box::new(Node::Boolean(false))),
-            Node::AssignVariable(
-                "midichlorian".to_string(),
-                Box::new(Node::Variable("luke".to_string())),
-                vec![Node::Binary(
-                    BinaryOperation::GreaterThan,
-                    Box::new(Node::Variable("anakin".to_string())),
-                )],
-            ),
-            Node::Print(Box::new(Node::Variable("midichlorian".to_string()))),
-            Node::AssignVariable(
-                "midichlorian".to_string(),
-                Box::new(Node::Variable("anakin".to_string())),
-                vec![Node::Binary(
-                    BinaryOperation::LessThan,
-                    Box::new(Node::Variable("leia".to_string())),
-                )],
-            ),
-            Node::Print(Box::new(Node::Variable("midichlorian".to_string()))),
-            Node::AssignVariable(
-                "midichlorian".to_string(),
-                Box::new(Node::Variable("leia".to_string())),
-                vec![Node::Binary(
-                    BinaryOperation::Equal,
-                    Box::new(Node::Variable("luke".to_string())),
-                )],
-            ),
-            Node::Print(Box::new(Node::Variable("midichlorian".to_string()))),
-        ])];
-
-        let result = evaluate(ast, input.lock(), &mut output);
-        assert!(result.is_ok());
-
-        let output = String::from_utf8(output).expect("Not UTF-8");
-        assert_eq!(
-            output,
-            "That's impossible!That's impossible!From a certain point of view."
-        );
-    }
-
-    #[test]
-    fn logic() {
+    fn grouping() {
         let input = io::stdin();
         let mut output = Vec::new();
         let ast = vec![Node::Main(vec![
-            Node::DeclareBoolean("lightside".to_string(), Box::new(Node::Boolean(true))),
-            Node::DeclareBoolean("darkside".to_string(), Box::new(Node::Boolean(false))),
-            Node::DeclareBoolean("revan".to_string(), Box::new(Node::Boolean(false))),
-            Node::AssignVariable(
-                "revan".to_string(),
-                Box::new(Node::Variable("lightside".to_string())),
-                vec![Node::Binary(
-                    BinaryOperation::Or,
-                    Box::new(Node::Variable("darkside".to_string())),
-                )],
-            ),
-            Node::Print(Box::new(Node::Variable("revan".to_string()))),
+            Node::DeclareFloat("ships".to_string(), Box::new(Node::Float(4.0))),
+            // 2 + (ships * (3 - 1))
             Node::AssignVariable(
-                "revan".to_string(),
-                Box::new(Node::Variable("revan".to_string())),
+                "ships".to_string(),
+                Box::new(Node::Float(2.0)),
                 vec![Node::Binary(
-                    BinaryOperation::And,
-                    Box::new(Node::Variable("lightside".to_string())),
+                    BinaryOperation::Add,
+                    Box::new(Node::Expression(
+                        Box::new(Node::Variable("ships".to_string())),
+                        vec![Node::Binary(
+                            BinaryOperation::Multiply,
+                            Box::new(Node::Expression(
+                                Box::new(Node::Float(3.0)),
+                                vec![Node::Binary(
+                                    BinaryOperation::Subtract,
+                                    Box::new(Node::Float(1.0)),
+                                )],
+                            )),
+                        )],
+                    )),
                 )],
             ),
-            Node::Print(Box::new(Node::Variable("revan".to_string()))),
+            Node::Print(Box::new(Node::Variable("ships".to_string()))),
+            // Chains without groups still go left to right: (2 + 4) * 3
             Node::AssignVariable(
-                "revan".to_string(),
-                Box::new(Node::Variable("revan".to_string())),
-                vec![Node::Unary(UnaryOperation::Not)],
+                "ships".to_string(),
+                Box::new(Node::Float(2.0)),
+                vec![
+                    Node::Binary(BinaryOperation::Add, Box::new(Node::Float(4.0))),
+                    Node::Binary(BinaryOperation::Multiply, Box::new(Node::Float(3.0))),
+                ],
             ),
-            Node::Print(Box::new(Node::Variable("revan".to_string()))),
+            Node::Print(Box::new(Node::Variable("ships".to_string()))),
         ])];
 
-        let result = evaluate(ast, input.lock(), &mut output);
+        let result = evaluate(ast, input.lock(), &mut output, &Options::default());
         assert!(result.is_ok());
 
         let output = String::from_utf8(output).expect("Not UTF-8");
-        assert_eq!(
-            output,
-            "From a certain point of view.From a certain point of view.That's impossible!"
-        );
+        assert_eq!(output, "1018");
     }
 
     #[test]
-    fn while_loop() {
+    fn expressions() {
         let input = io::stdin();
         let mut output = Vec::new();
         let ast = vec![Node::Main(vec![
-            Node::DeclareFloat("deathStars".to_string(), Box::new(Node::Float(3.0))),
+            Node::DeclareFloat("ships".to_string(), Box::new(Node::Float(0.0))),
+            Node::DeclareFloat("waves".to_string(), Box::new(Node::Float(0.0))),
+            // The condition is worked out again each time
             Node::While(
-                Box::new(Node::Variable("deathStars".to_string())),
+                Box::new(Node::Expression(
+                    Box::new(Node::Variable("ships".to_string())),
+                    vec![Node::Binary(
+                        BinaryOperation::LessThan,
+                        Box::new(Node::Float(3.0)),
+                    )],
+                )),
                 vec![
-                    Node::Print(Box::new(Node::Variable("deathStars".to_string()))),
+                    Node::Print(Box::new(Node::Expression(
+                        Box::new(Node::Variable("ships".to_string())),
+                        vec![Node::Binary(
+                            BinaryOperation::Multiply,
+                            Box::new(Node::Float(10.0)),
+                        )],
+                    ))),
                     Node::AssignVariable(
-                        "deathStars".to_string(),
-                        Box::new(Node::Variable("deathStars".to_string())),
+                        "ships".to_string(),
+                        Box::new(Node::Variable("ships".to_string())),
                         vec![Node::Binary(
-                            BinaryOperation::Subtract,
+                            BinaryOperation::Add,
                             Box::new(Node::Float(1.0)),
                         )],
                     ),
                 ],
             ),
+            Node::For(
+                Box::new(Node::Expression(
+                    Box::new(Node::Variable("ships".to_string())),
+                    vec![Node::Binary(
+                        BinaryOperation::Subtract,
+                        Box::new(Node::Float(1.0)),
+                    )],
+                )),
+                Box::new(Node::Variable("waves".to_string())),
+                vec![Node::Print(Box::new(Node::String("!".to_string())))],
+            ),
         ])];
 
-        let result = evaluate(ast, input.lock(), &mut output);
+        let result = evaluate(ast, input.lock(), &mut output, &Options::default());
         assert!(result.is_ok());
 
         let output = String::from_utf8(output).expect("Not UTF-8");
-        assert_eq!(output, "321");
+        assert_eq!(output, "01020!!");
     }
 
     #[test]
-    fn for_loop() {
+    fn integers() {
         let input = io::stdin();
         let mut output = Vec::new();
         let ast = vec![Node::Main(vec![
-            Node::DeclareFloat("deadYounglings".to_string(), Box::new(Node::Float(0.0))),
-            Node::For(
-                Box::new(Node::Float(10.0)),
-                Box::new(Node::Variable("deadYounglings".to_string())),
-                vec![Node::Print(Box::new(Node::Variable(
-                    "deadYounglings".to_string(),
-                )))],
+            // Past where floats can count by one
+            Node::DeclareInteger("clones".to_string(), Box::new(Node::Integer(16_777_216))),
+            Node::AssignVariable(
+                "clones".to_string(),
+                Box::new(Node::Variable("clones".to_string())),
+                vec![Node::Binary(
+                    BinaryOperation::Add,
+                    Box::new(Node::Integer(1)),
+                )],
             ),
+            Node::Print(Box::new(Node::Variable("clones".to_string()))),
+            Node::Print(Box::new(Node::String(" ".to_string()))),
+            // Integer division and modulus
+            Node::Print(Box::new(Node::Expression(
+                Box::new(Node::Integer(7)),
+                vec![Node::Binary(
+                    BinaryOperation::Divide,
+                    Box::new(Node::Integer(2)),
+                )],
+            ))),
+            Node::Print(Box::new(Node::Expression(
+                Box::new(Node::Integer(7)),
+                vec![Node::Binary(
+                    BinaryOperation::Modulus,
+                    Box::new(Node::Integer(2)),
+                )],
+            ))),
+            Node::Print(Box::new(Node::String(" ".to_string()))),
+            // Mixing in a float makes a float
+            Node::Print(Box::new(Node::Expression(
+                Box::new(Node::Integer(7)),
+                vec![Node::Binary(
+                    BinaryOperation::Divide,
+                    Box::new(Node::Float(2.0)),
+                )],
+            ))),
+            Node::Print(Box::new(Node::String(" ".to_string()))),
+            // Integers widen into float variables
+            Node::DeclareFloat("speed".to_string(), Box::new(Node::Integer(3))),
+            Node::AssignVariable("speed".to_string(), Box::new(Node::Integer(10)), vec![]),
+            Node::Print(Box::new(Node::Expression(
+                Box::new(Node::Variable("speed".to_string())),
+                vec![Node::Binary(
+                    BinaryOperation::Divide,
+                    Box::new(Node::Integer(4)),
+                )],
+            ))),
+            Node::Print(Box::new(Node::String(" ".to_string()))),
+            Node::Print(Box::new(Node::Expression(
+                Box::new(Node::Float(-2.7)),
+                vec![Node::Unary(UnaryOperation::ToInteger)],
+            ))),
         ])];
 
-        let result = evaluate(ast, input.lock(), &mut output);
+        let result = evaluate(ast, input.lock(), &mut output, &Options::default());
         assert!(result.is_ok());
 
         let output = String::from_utf8(output).expect("Not UTF-8");
-        assert_eq!(output, "0123456789");
+        assert_eq!(output, "16777217 31 3.5 2.5 -2");
 
-        // For loop with variable
+        // Whole numbers given to a float variable are read as floats, except for indexes
+        let input = io::stdin();
         let mut output = Vec::new();
+        let half = Node::Expression(
+            Box::new(Node::Integer(7)),
+            vec![Node::Binary(
+                BinaryOperation::Divide,
+                Box::new(Node::Integer(2)),
+            )],
+        );
         let ast = vec![Node::Main(vec![
-            Node::DeclareFloat("deadYounglings".to_string(), Box::new(Node::Float(0.0))),
-            Node::DeclareFloat("lightsaberSwings".to_string(), Box::new(Node::Float(10.0))),
-            Node::For(
-                Box::new(Node::Variable("lightsaberSwings".to_string())),
-                Box::new(Node::Variable("deadYounglings".to_string())),
-                vec![Node::Print(Box::new(Node::Variable(
-                    "deadYounglings".to_string(),
-                )))],
+            Node::DeclareFloat("half".to_string(), Box::new(half.clone())),
+            Node::Print(Box::new(Node::Variable("half".to_string()))),
+            Node::Print(Box::new(Node::String(" ".to_string()))),
+            Node::DeclareFloat("speed".to_string(), Box::new(Node::Float(0.0))),
+            Node::AssignVariable(
+                "speed".to_string(),
+                Box::new(Node::Integer(3_000_000_000)),
+                vec![
+                    Node::Binary(
+                        BinaryOperation::Multiply,
+                        Box::new(Node::Integer(4_000_000_000)),
+                    ),
+                    Node::Binary(
+                        BinaryOperation::Multiply,
+                        Box::new(Node::Integer(4_000_000_000)),
+                    ),
+                ],
+            ),
+            Node::AssignVariable("speed".to_string(), Box::new(half), vec![]),
+            Node::Print(Box::new(Node::Variable("speed".to_string()))),
+            Node::Print(Box::new(Node::String(" ".to_string()))),
+            Node::DeclareList("speeds".to_string()),
+            Node::Append("speeds".to_string(), Box::new(Node::Float(1.5))),
+            Node::DeclareFloat(
+                "first".to_string(),
+                Box::new(Node::Expression(
+                    Box::new(Node::Variable("speeds".to_string())),
+                    vec![Node::Binary(
+                        BinaryOperation::Index,
+                        Box::new(Node::Integer(0)),
+                    )],
+                )),
             ),
+            Node::Print(Box::new(Node::Variable("first".to_string()))),
         ])];
 
-        let result = evaluate(ast, input.lock(), &mut output);
+        let result = evaluate(ast, input.lock(), &mut output, &Options::default());
         assert!(result.is_ok());
 
         let output = String::from_utf8(output).expect("Not UTF-8");
-        assert_eq!(output, "0123456789");
-    }
+        assert_eq!(output, "3.5 3.5 1.5");
 
-    #[test]
-    fn if_else() {
+        // Integers cannot become floats without a conversion
         let input = io::stdin();
         let mut output = Vec::new();
-        let ast = vec![Node::Main(vec![Node::If(
-            Box::new(Node::Boolean(true)),
+        let ast = vec![Node::Main(vec![
+            Node::DeclareInteger("clones".to_string(), Box::new(Node::Integer(1))),
+            Node::AssignVariable("clones".to_string(), Box::new(Node::Float(1.5)), vec![]),
+        ])];
+
+        let result = evaluate(ast, input.lock(), &mut output, &Options::default());
+        assert!(matches!(result, Err(Error::Type(_))));
+
+        let input = io::stdin();
+        let mut output = Vec::new();
+        let ast = vec![Node::Main(vec![Node::Print(Box::new(Node::Expression(
+            Box::new(Node::Integer(i64::MAX)),
+            vec![Node::Binary(
+                BinaryOperation::Add,
+                Box::new(Node::Integer(1)),
+            )],
+        )))])];
+
+        let result = evaluate(ast, input.lock(), &mut output, &Options::default());
+        assert!(matches!(result, Err(Error::Runtime(_))));
+
+        let input = io::stdin();
+        let mut output = Vec::new();
+        let ast = vec![Node::Main(vec![Node::Print(Box::new(Node::Expression(
+            Box::new(Node::Integer(1)),
+            vec![Node::Binary(
+                BinaryOperation::Divide,
+                Box::new(Node::Integer(0)),
+            )],
+        )))])];
+
+        let result = evaluate(ast, input.lock(), &mut output, &Options::default());
+        assert!(matches!(result, Err(Error::Runtime(_))));
+    }
+
+    #[test]
+    fn formatting() {
+        let input = io::stdin();
+        let mut output = Vec::new();
+        let ast = vec![Node::Main(vec![
+            Node::DeclareFloat("credits".to_string(), Box::new(Node::Float(0.1))),
+            Node::AssignVariable(
+                "credits".to_string(),
+                Box::new(Node::Variable("credits".to_string())),
+                vec![Node::Binary(
+                    BinaryOperation::Add,
+                    Box::new(Node::Float(0.2)),
+                )],
+            ),
+            Node::Print(Box::new(Node::Variable("credits".to_string()))),
+            Node::Print(Box::new(Node::String(" ".to_string()))),
+            Node::FormatFixed(Box::new(Node::Integer(2))),
+            Node::Print(Box::new(Node::Variable("credits".to_string()))),
+            Node::Print(Box::new(Node::String(" ".to_string()))),
+            // Floats inside other values are formatted too, but integers are not
+            Node::DeclareList("prices".to_string()),
+            Node::Append("prices".to_string(), Box::new(Node::Float(1.5))),
+            Node::Print(Box::new(Node::Variable("prices".to_string()))),
+            Node::Print(Box::new(Node::Integer(7))),
+            Node::Print(Box::new(Node::String(" ".to_string()))),
+            Node::FormatScientific,
+            Node::Print(Box::new(Node::Float(1500.0))),
+            Node::Print(Box::new(Node::String(" ".to_string()))),
+            Node::FormatDefault,
+            Node::Print(Box::new(Node::Float(1500.0))),
+            Node::Print(Box::new(Node::String(" ".to_string()))),
+            // Only integers that have a numeral are written as one
+            Node::FormatRoman,
+            Node::Print(Box::new(Node::Integer(1977))),
+            Node::Print(Box::new(Node::String(" ".to_string()))),
+            Node::Print(Box::new(Node::Integer(0))),
+            Node::Print(Box::new(Node::String(" ".to_string()))),
+            Node::Print(Box::new(Node::Float(4.5))),
+        ])];
+
+        let result = evaluate(ast, input.lock(), &mut output, &Options::default());
+        assert!(result.is_ok());
+
+        let output = String::from_utf8(output).expect("Not UTF-8");
+        assert_eq!(
+            output,
+            "0.30000000000000004 0.30 [1.50]7 1.5e3 1500 MCMLXXVII 0 4.5"
+        );
+
+        let input = io::stdin();
+        let mut output = Vec::new();
+        let ast = vec![Node::Main(vec![Node::FormatFixed(Box::new(
+            Node::Integer(-1),
+        ))])];
+
+        let result = evaluate(ast, input.lock(), &mut output, &Options::default());
+        assert!(matches!(result, Err(Error::Runtime(_))));
+    }
+
+    #[test]
+    fn strings() {
+        let input = io::stdin();
+        let mut output = Vec::new();
+        let ast = vec![
+            // Replaces the built in upper
+            Node::DeclareFunction(
+                "upper".to_string(),
+                vec![Node::Variable("name".to_string())],
+                vec![Node::Return(Box::new(Node::String("LOUD".to_string())))],
+                false,
+                None,
+            ),
+            Node::Main(vec![
+                Node::DeclareString(
+                    "name".to_string(),
+                    Box::new(Node::String("Obi".to_string())),
+                ),
+                Node::AssignVariable(
+                    "name".to_string(),
+                    Box::new(Node::Variable("name".to_string())),
+                    vec![Node::Binary(
+                        BinaryOperation::Add,
+                        Box::new(Node::String("-Wan ☀".to_string())),
+                    )],
+                ),
+                Node::Print(Box::new(Node::Variable("name".to_string()))),
+                Node::Print(Box::new(Node::Expression(
+                    Box::new(Node::Variable("name".to_string())),
+                    vec![Node::Unary(UnaryOperation::Length)],
+                ))),
+                Node::AssignVariable(
+                    "name".to_string(),
+                    Box::new(Node::CallFunction(
+                        "lower".to_string(),
+                        vec![Node::Variable("name".to_string())],
+                    )),
+                    vec![],
+                ),
+                Node::Print(Box::new(Node::Variable("name".to_string()))),
+                Node::AssignVariable(
+                    "name".to_string(),
+                    Box::new(Node::CallFunction(
+                        "upper".to_string(),
+                        vec![Node::Variable("name".to_string())],
+                    )),
+                    vec![],
+                ),
+                Node::Print(Box::new(Node::Variable("name".to_string()))),
+            ]),
+        ];
+
+        let result = evaluate(ast, input.lock(), &mut output, &Options::default());
+        assert!(result.is_ok());
+
+        let output = String::from_utf8(output).expect("Not UTF-8");
+        assert_eq!(output, "Obi-Wan ☀9obi-wan ☀LOUD");
+
+        // Strings only add to strings
+        let input = io::stdin();
+        let mut output = Vec::new();
+        let ast = vec![Node::Main(vec![Node::Print(Box::new(Node::Expression(
+            Box::new(Node::String("R".to_string())),
+            vec![Node::Binary(
+                BinaryOperation::Add,
+                Box::new(Node::Integer(2)),
+            )],
+        )))])];
+
+        let result = evaluate(ast, input.lock(), &mut output, &Options::default());
+        assert!(matches!(result, Err(Error::Type(_))));
+    }
+
+    #[test]
+    fn interpolation() {
+        let input = io::stdin();
+        let mut output = Vec::new();
+        let ast = vec![Node::Main(vec![
+            Node::DeclareFloat("speed".to_string(), Box::new(Node::Float(1.5))),
+            Node::DeclareString(
+                "name".to_string(),
+                Box::new(Node::Interpolation(vec![
+                    Node::String("Falcon ".to_string()),
+                    Node::Variable("speed".to_string()),
+                ])),
+            ),
+            Node::PrintLine(Box::new(Node::Variable("name".to_string()))),
+            // Placeholders use the number format
+            Node::FormatFixed(Box::new(Node::Integer(2))),
+            Node::PrintLine(Box::new(Node::Interpolation(vec![
+                Node::Variable("speed".to_string()),
+                Node::String(" past lightspeed".to_string()),
+            ]))),
+        ])];
+
+        let result = evaluate(ast, input.lock(), &mut output, &Options::default());
+        assert!(result.is_ok());
+
+        let output = String::from_utf8(output).expect("Not UTF-8");
+        assert_eq!(output, "Falcon 1.5\n1.50 past lightspeed\n");
+
+        let input = io::stdin();
+        let mut output = Vec::new();
+        let ast = vec![Node::Main(vec![Node::Print(Box::new(
+            Node::Interpolation(vec![Node::Variable("ghost".to_string())]),
+        ))])];
+
+        let result = evaluate(ast, input.lock(), &mut output, &Options::default());
+        assert!(matches!(result, Err(Error::Runtime(_))));
+    }
+
+    #[test]
+    fn conversions() {
+        let input = io::stdin();
+        let mut output = Vec::new();
+        let ast = vec![Node::Main(vec![
+            Node::Print(Box::new(Node::Expression(
+                Box::new(Node::String(" 42 ".to_string())),
+                vec![
+                    Node::Unary(UnaryOperation::ToInteger),
+                    Node::Binary(BinaryOperation::Add, Box::new(Node::Integer(1))),
+                ],
+            ))),
+            Node::Print(Box::new(Node::String(" ".to_string()))),
+            Node::Print(Box::new(Node::Expression(
+                Box::new(Node::String("1.5e1".to_string())),
+                vec![Node::Unary(UnaryOperation::ToFloat)],
+            ))),
+            Node::Print(Box::new(Node::String(" ".to_string()))),
+            Node::Print(Box::new(Node::Expression(
+                Box::new(Node::Integer(7)),
+                vec![
+                    Node::Unary(UnaryOperation::ToString),
+                    Node::Binary(
+                        BinaryOperation::Add,
+                        Box::new(Node::String("!".to_string())),
+                    ),
+                ],
+            ))),
+            Node::Print(Box::new(Node::String(" ".to_string()))),
+            Node::Print(Box::new(Node::Expression(
+                Box::new(Node::String("That's impossible!".to_string())),
+                vec![
+                    Node::Unary(UnaryOperation::ToBoolean),
+                    Node::Unary(UnaryOperation::Not),
+                ],
+            ))),
+            Node::Print(Box::new(Node::String(" ".to_string()))),
+            Node::Print(Box::new(Node::Expression(
+                Box::new(Node::Float(0.0)),
+                vec![Node::Unary(UnaryOperation::ToBoolean)],
+            ))),
+        ])];
+
+        let result = evaluate(ast, input.lock(), &mut output, &Options::default());
+        assert!(result.is_ok());
+
+        let output = String::from_utf8(output).expect("Not UTF-8");
+        assert_eq!(
+            output,
+            "43 15 7! From a certain point of view. That's impossible!"
+        );
+    }
+
+    #[test]
+    fn try_catch() {
+        let input = io::stdin();
+        let mut output = Vec::new();
+        let ast = vec![
+            Node::DeclareFunction(
+                "Parse".to_string(),
+                vec![Node::Variable("text".to_string())],
+                vec![Node::Return(Box::new(Node::Expression(
+                    Box::new(Node::Variable("text".to_string())),
+                    vec![Node::Unary(UnaryOperation::ToFloat)],
+                )))],
+                false,
+                None,
+            ),
+            Node::Main(vec![
+                Node::DeclareFloat("speed".to_string(), Box::new(Node::Float(1.0))),
+                Node::Try(
+                    vec![
+                        Node::AssignVariable(
+                            "speed".to_string(),
+                            Box::new(Node::CallFunction(
+                                "Parse".to_string(),
+                                vec![Node::String("fast".to_string())],
+                            )),
+                            vec![],
+                        ),
+                        Node::Print(Box::new(Node::String("Not printed".to_string()))),
+                    ],
+                    Some("problem".to_string()),
+                    vec![Node::Print(Box::new(Node::Variable("problem".to_string())))],
+                ),
+                // Back in main after the function failed
+                Node::Print(Box::new(Node::Variable("speed".to_string()))),
+                Node::Try(
+                    vec![Node::Print(Box::new(Node::String("Fine".to_string())))],
+                    None,
+                    vec![Node::Print(Box::new(Node::String(
+                        "Not printed".to_string(),
+                    )))],
+                ),
+            ]),
+        ];
+
+        let result = evaluate(ast, input.lock(), &mut output, &Options::default());
+        assert!(result.is_ok());
+
+        let output = String::from_utf8(output).expect("Not UTF-8");
+        assert_eq!(output, "Cannot convert \"fast\" to a float1Fine");
+
+        // Type errors are mistakes in the program, so they are not caught
+        let input = io::stdin();
+        let mut output = Vec::new();
+        let ast = vec![Node::Main(vec![Node::Try(
+            vec![Node::Print(Box::new(Node::Expression(
+                Box::new(Node::String("R".to_string())),
+                vec![Node::Binary(
+                    BinaryOperation::Subtract,
+                    Box::new(Node::Integer(2)),
+                )],
+            )))],
+            None,
+            vec![],
+        )])];
+
+        let result = evaluate(ast, input.lock(), &mut output, &Options::default());
+        assert!(matches!(result, Err(Error::Type(_))));
+    }
+
+    #[test]
+    fn natives() {
+        let logged = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let log = Arc::clone(&logged);
+
+        let mut natives = Natives::new();
+        natives.register(
+            "Parsecs",
+            vec![Type::Float],
+            Some(Type::Float),
+            |arguments| match arguments {
+                [Node::Float(distance)] => Ok(Node::Float(distance / 3.26)),
+                _ => unreachable!(),
+            },
+        );
+        natives.register("Log", vec![Type::String], None, move |arguments| {
+            log.lock().unwrap().push(arguments[0].clone());
+            Ok(Node::Noop)
+        });
+        // Replaces the built in one
+        natives.register("upper", vec![Type::String], Some(Type::String), |_| {
+            Ok(Node::String("NATIVE".to_string()))
+        });
+        natives.register("Broken", vec![], Some(Type::Integer), |_| {
+            Ok(Node::String("Not an integer".to_string()))
+        });
+        let options = Options {
+            natives,
+            ..Options::default()
+        };
+
+        let input = io::stdin();
+        let mut output = Vec::new();
+        let ast = vec![Node::Main(vec![
+            Node::DeclareFloat("distance".to_string(), Box::new(Node::Float(0.0))),
+            // Integers become floats on the way in
+            Node::AssignVariable(
+                "distance".to_string(),
+                Box::new(Node::CallFunction(
+                    "Parsecs".to_string(),
+                    vec![Node::Integer(326)],
+                )),
+                vec![],
+            ),
+            Node::Print(Box::new(Node::Variable("distance".to_string()))),
+            Node::CallFunction(
+                "Log".to_string(),
+                vec![Node::String("Kessel Run".to_string())],
+            ),
+            Node::DeclareString("name".to_string(), Box::new(Node::String("".to_string()))),
+            Node::AssignVariable(
+                "name".to_string(),
+                Box::new(Node::CallFunction(
+                    "upper".to_string(),
+                    vec![Node::String("Falcon".to_string())],
+                )),
+                vec![],
+            ),
+            Node::Print(Box::new(Node::Variable("name".to_string()))),
+        ])];
+
+        let result = evaluate(ast, input.lock(), &mut output, &options);
+        assert_eq!(result, Ok(0));
+
+        let output = String::from_utf8(output).expect("Not UTF-8");
+        assert_eq!(output, "100NATIVE");
+        assert_eq!(
+            *logged.lock().unwrap(),
+            vec![Node::String("Kessel Run".to_string())]
+        );
+
+        // Arguments and return values are checked against the declared types
+        let input = io::stdin();
+        let mut output = Vec::new();
+        let ast = vec![Node::Main(vec![Node::CallFunction(
+            "Log".to_string(),
+            vec![Node::Integer(12)],
+        )])];
+
+        let result = evaluate(ast, input.lock(), &mut output, &options);
+        assert_eq!(
+            result,
+            Err(Error::Type(
+                "Log expected argument 1 to be of type string, not integer".to_string()
+            ))
+        );
+
+        let input = io::stdin();
+        let mut output = Vec::new();
+        let ast = vec![Node::Main(vec![Node::CallFunction(
+            "Broken".to_string(),
+            vec![],
+        )])];
+
+        let result = evaluate(ast, input.lock(), &mut output, &options);
+        assert_eq!(
+            result,
+            Err(Error::Type(
+                "Broken must return a value of type integer, not string".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn initial_values() {
+        let input = io::stdin();
+        let mut output = Vec::new();
+        let ast = vec![
+            Node::DeclareFunction(
+                "Double".to_string(),
+                vec![Node::Variable("x".to_string())],
+                vec![Node::Return(Box::new(Node::Expression(
+                    Box::new(Node::Variable("x".to_string())),
+                    vec![Node::Binary(
+                        BinaryOperation::Multiply,
+                        Box::new(Node::Integer(2)),
+                    )],
+                )))],
+                false,
+                None,
+            ),
+            Node::Main(vec![
+                Node::DeclareInteger("troopers".to_string(), Box::new(Node::Integer(3))),
+                Node::DeclareInteger(
+                    "squad".to_string(),
+                    Box::new(Node::Variable("troopers".to_string())),
+                ),
+                // Integers still widen into floats
+                Node::DeclareFloat(
+                    "half".to_string(),
+                    Box::new(Node::Expression(
+                        Box::new(Node::Variable("squad".to_string())),
+                        vec![Node::Binary(
+                            BinaryOperation::Subtract,
+                            Box::new(Node::Integer(1)),
+                        )],
+                    )),
+                ),
+                Node::DeclareInteger(
+                    "legion".to_string(),
+                    Box::new(Node::Expression(
+                        Box::new(Node::CallFunction(
+                            "Double".to_string(),
+                            vec![Node::Variable("squad".to_string())],
+                        )),
+                        vec![Node::Binary(
+                            BinaryOperation::Add,
+                            Box::new(Node::Integer(1)),
+                        )],
+                    )),
+                ),
+                Node::Print(Box::new(Node::Variable("squad".to_string()))),
+                Node::Print(Box::new(Node::Variable("half".to_string()))),
+                Node::Print(Box::new(Node::Variable("legion".to_string()))),
+            ]),
+        ];
+
+        let result = evaluate(ast, input.lock(), &mut output, &Options::default());
+        assert!(result.is_ok());
+
+        let output = String::from_utf8(output).expect("Not UTF-8");
+        assert_eq!(output, "327");
+
+        // The value must still be the declared type
+        let input = io::stdin();
+        let mut output = Vec::new();
+        let ast = vec![Node::Main(vec![
+            Node::DeclareString("name".to_string(), Box::new(Node::String("R2".to_string()))),
+            Node::DeclareBoolean(
+                "droid".to_string(),
+                Box::new(Node::Variable("name".to_string())),
+            ),
+        ])];
+
+        let result = evaluate(ast, input.lock(), &mut output, &Options::default());
+        assert!(matches!(result, Err(Error::Type(_))));
+    }
+
+    #[test]
+    fn globals() {
+        let input = io::stdin();
+        let mut output = Vec::new();
+        let ast = vec![
+            Node::DeclareInteger("kills".to_string(), Box::new(Node::Integer(0))),
+            Node::DeclareString(
+                "pilot".to_string(),
+                Box::new(Node::String("Luke".to_string())),
+            ),
+            Node::DeclareFunction(
+                "Shoot".to_string(),
+                vec![],
+                vec![
+                    Node::AssignVariable(
+                        "kills".to_string(),
+                        Box::new(Node::Variable("kills".to_string())),
+                        vec![Node::Binary(
+                            BinaryOperation::Add,
+                            Box::new(Node::Integer(1)),
+                        )],
+                    ),
+                    // Only hides the global inside this call
+                    Node::DeclareString(
+                        "pilot".to_string(),
+                        Box::new(Node::String("Wedge".to_string())),
+                    ),
+                    Node::Print(Box::new(Node::Variable("pilot".to_string()))),
+                ],
+                true,
+                None,
+            ),
+            Node::Main(vec![
+                Node::CallFunction("Shoot".to_string(), vec![]),
+                Node::CallFunction("Shoot".to_string(), vec![]),
+                Node::Print(Box::new(Node::Variable("kills".to_string()))),
+                Node::Print(Box::new(Node::Variable("pilot".to_string()))),
+            ]),
+        ];
+
+        let result = evaluate(ast, input.lock(), &mut output, &Options::default());
+        assert!(result.is_ok());
+
+        let output = String::from_utf8(output).expect("Not UTF-8");
+        assert_eq!(output, "WedgeWedge2Luke");
+
+        // Functions called to work out a global can use the globals declared before it
+        let input = io::stdin();
+        let mut output = Vec::new();
+        let ast = vec![
+            Node::DeclareInteger("kills".to_string(), Box::new(Node::Integer(2))),
+            Node::DeclareInteger(
+                "score".to_string(),
+                Box::new(Node::CallFunction("Score".to_string(), vec![])),
+            ),
+            Node::DeclareFunction(
+                "Score".to_string(),
+                vec![],
+                vec![
+                    Node::AssignVariable(
+                        "kills".to_string(),
+                        Box::new(Node::Variable("kills".to_string())),
+                        vec![Node::Binary(
+                            BinaryOperation::Add,
+                            Box::new(Node::Integer(1)),
+                        )],
+                    ),
+                    Node::Return(Box::new(Node::Expression(
+                        Box::new(Node::Variable("kills".to_string())),
+                        vec![Node::Binary(
+                            BinaryOperation::Multiply,
+                            Box::new(Node::Integer(10)),
+                        )],
+                    ))),
+                ],
+                false,
+                None,
+            ),
+            Node::Main(vec![
+                Node::Print(Box::new(Node::Variable("kills".to_string()))),
+                Node::Print(Box::new(Node::Variable("score".to_string()))),
+            ]),
+        ];
+
+        let result = evaluate(ast, input.lock(), &mut output, &Options::default());
+        assert!(result.is_ok());
+
+        let output = String::from_utf8(output).expect("Not UTF-8");
+        assert_eq!(output, "330");
+
+        let input = io::stdin();
+        let mut output = Vec::new();
+        let ast = vec![
+            Node::DeclareInteger("kills".to_string(), Box::new(Node::Integer(0))),
+            Node::DeclareInteger("kills".to_string(), Box::new(Node::Integer(1))),
+            Node::Main(vec![]),
+        ];
+
+        let result = evaluate(ast, input.lock(), &mut output, &Options::default());
+        assert!(matches!(result, Err(Error::Runtime(_))));
+
+        // Globals keep their type like any other variable
+        let input = io::stdin();
+        let mut output = Vec::new();
+        let ast = vec![
+            Node::DeclareInteger("kills".to_string(), Box::new(Node::Integer(0))),
+            Node::Main(vec![Node::AssignVariable(
+                "kills".to_string(),
+                Box::new(Node::String("Many".to_string())),
+                vec![],
+            )]),
+        ];
+
+        let result = evaluate(ast, input.lock(), &mut output, &Options::default());
+        assert!(matches!(result, Err(Error::Type(_))));
+    }
+
+    #[test]
+    fn constants() {
+        let input = io::stdin();
+        let mut output = Vec::new();
+        let ast = vec![
+            Node::DeclareConstant(Box::new(Node::DeclareFloat(
+                "taxRate".to_string(),
+                Box::new(Node::Float(0.5)),
+            ))),
+            Node::Main(vec![
+                Node::DeclareConstant(Box::new(Node::DeclareInteger(
+                    "price".to_string(),
+                    Box::new(Node::Integer(10)),
+                ))),
+                // A block can still declare a variable of its own with the same name
+                Node::If(
+                    Box::new(Node::Boolean(true)),
+                    vec![
+                        Node::DeclareInteger("price".to_string(), Box::new(Node::Integer(1))),
+                        Node::AssignVariable(
+                            "price".to_string(),
+                            Box::new(Node::Integer(2)),
+                            vec![],
+                        ),
+                        Node::Print(Box::new(Node::Variable("price".to_string()))),
+                    ],
+                    vec![],
+                ),
+                Node::Print(Box::new(Node::Expression(
+                    Box::new(Node::Variable("price".to_string())),
+                    vec![Node::Binary(
+                        BinaryOperation::Multiply,
+                        Box::new(Node::Variable("taxRate".to_string())),
+                    )],
+                ))),
+            ]),
+        ];
+
+        let result = evaluate(ast, input.lock(), &mut output, &Options::default());
+        assert!(result.is_ok());
+
+        let output = String::from_utf8(output).expect("Not UTF-8");
+        assert_eq!(output, "25");
+
+        let input = io::stdin();
+        let mut output = Vec::new();
+        let ast = vec![Node::Main(vec![
+            Node::DeclareConstant(Box::new(Node::DeclareInteger(
+                "price".to_string(),
+                Box::new(Node::Integer(10)),
+            ))),
+            Node::AssignVariable("price".to_string(), Box::new(Node::Integer(11)), vec![]),
+        ])];
+
+        let result = evaluate(ast, input.lock(), &mut output, &Options::default());
+        assert!(matches!(result, Err(Error::Runtime(_))));
+
+        // Globals stay constant inside functions too
+        let input = io::stdin();
+        let mut output = Vec::new();
+        let ast = vec![
+            Node::DeclareConstant(Box::new(Node::DeclareFloat(
+                "taxRate".to_string(),
+                Box::new(Node::Float(0.5)),
+            ))),
+            Node::DeclareFunction(
+                "Raise".to_string(),
+                vec![],
+                vec![Node::AssignVariable(
+                    "taxRate".to_string(),
+                    Box::new(Node::Float(0.9)),
+                    vec![],
+                )],
+                true,
+                None,
+            ),
+            Node::Main(vec![Node::CallFunction("Raise".to_string(), vec![])]),
+        ];
+
+        let result = evaluate(ast, input.lock(), &mut output, &Options::default());
+        assert!(matches!(result, Err(Error::Runtime(_))));
+    }
+
+    #[test]
+    fn block_scopes() {
+        let input = io::stdin();
+        let mut output = Vec::new();
+        let ast = vec![Node::Main(vec![
+            Node::DeclareInteger("total".to_string(), Box::new(Node::Integer(0))),
+            Node::DeclareInteger("wave".to_string(), Box::new(Node::Integer(0))),
+            Node::DeclareString(
+                "pilot".to_string(),
+                Box::new(Node::String("Luke".to_string())),
+            ),
+            Node::For(
+                Box::new(Node::Integer(3)),
+                Box::new(Node::Variable("wave".to_string())),
+                vec![
+                    // Declared again each time around the loop
+                    Node::DeclareInteger("fighters".to_string(), Box::new(Node::Integer(2))),
+                    Node::DeclareString(
+                        "pilot".to_string(),
+                        Box::new(Node::String("Wedge".to_string())),
+                    ),
+                    Node::AssignVariable(
+                        "total".to_string(),
+                        Box::new(Node::Variable("total".to_string())),
+                        vec![Node::Binary(
+                            BinaryOperation::Add,
+                            Box::new(Node::Variable("fighters".to_string())),
+                        )],
+                    ),
+                    Node::Print(Box::new(Node::Variable("pilot".to_string()))),
+                ],
+            ),
+            Node::Print(Box::new(Node::Variable("total".to_string()))),
+            Node::Print(Box::new(Node::Variable("pilot".to_string()))),
+        ])];
+
+        let result = evaluate(ast, input.lock(), &mut output, &Options::default());
+        assert!(result.is_ok());
+
+        let output = String::from_utf8(output).expect("Not UTF-8");
+        assert_eq!(output, "WedgeWedgeWedge6Luke");
+
+        // Variables are gone once their block ends
+        let input = io::stdin();
+        let mut output = Vec::new();
+        let ast = vec![Node::Main(vec![
+            Node::If(
+                Box::new(Node::Boolean(true)),
+                vec![Node::DeclareInteger(
+                    "fighters".to_string(),
+                    Box::new(Node::Integer(2)),
+                )],
+                vec![],
+            ),
+            Node::Print(Box::new(Node::Variable("fighters".to_string()))),
+        ])];
+
+        let result = evaluate(ast, input.lock(), &mut output, &Options::default());
+        assert!(matches!(result, Err(Error::Runtime(_))));
+
+        // Assigning a name that was never declared adds it to the function, not the block
+        let input = io::stdin();
+        let mut output = Vec::new();
+        let ast = vec![Node::Main(vec![
+            Node::If(
+                Box::new(Node::Boolean(true)),
+                vec![Node::AssignVariable(
+                    "speed".to_string(),
+                    Box::new(Node::Float(1.5)),
+                    vec![],
+                )],
+                vec![],
+            ),
+            Node::Print(Box::new(Node::Variable("speed".to_string()))),
+        ])];
+
+        let result = evaluate(ast, input.lock(), &mut output, &Options::default());
+        assert!(result.is_ok());
+
+        let output = String::from_utf8(output).expect("Not UTF-8");
+        assert_eq!(output, "1.5");
+
+        let input = io::stdin();
+        let mut output = Vec::new();
+        let ast = vec![Node::Main(vec![
+            Node::DeclareList("ships".to_string()),
+            Node::Append(
+                "ships".to_string(),
+                Box::new(Node::String("X-wing".to_string())),
+            ),
+            Node::ForEach(
+                Box::new(Node::Variable("ships".to_string())),
+                Box::new(Node::Variable("ship".to_string())),
+                vec![],
+            ),
+            Node::Print(Box::new(Node::Variable("ship".to_string()))),
+        ])];
+
+        let result = evaluate(ast, input.lock(), &mut output, &Options::default());
+        assert!(matches!(result, Err(Error::Runtime(_))));
+    }
+
+    #[test]
+    fn equality() {
+        let input = io::stdin();
+        let mut output = Vec::new();
+        let ast = vec![Node::Main(vec![
+            Node::DeclareFloat("anakin".to_string(), Box::new(Node::Float(27700.0))),
+            Node::DeclareFloat("luke".to_string(), Box::new(Node::Float(14500.0))),
+            Node::DeclareFloat("leia".to_string(), Box::new(Node::Float(14500.0))),
+            Node::DeclareBoolean("midichlorian".to_string(), Box::new(Node::Boolean(false))),
+            Node::AssignVariable(
+                "midichlorian".to_string(),
+                Box::new(Node::Variable("luke".to_string())),
+                vec![Node::Binary(
+                    BinaryOperation::GreaterThan,
+                    Box::new(Node::Variable("anakin".to_string())),
+                )],
+            ),
+            Node::Print(Box::new(Node::Variable("midichlorian".to_string()))),
+            Node::AssignVariable(
+                "midichlorian".to_string(),
+                Box::new(Node::Variable("anakin".to_string())),
+                vec![Node::Binary(
+                    BinaryOperation::LessThan,
+                    Box::new(Node::Variable("leia".to_string())),
+                )],
+            ),
+            Node::Print(Box::new(Node::Variable("midichlorian".to_string()))),
+            Node::AssignVariable(
+                "midichlorian".to_string(),
+                Box::new(Node::Variable("leia".to_string())),
+                vec![Node::Binary(
+                    BinaryOperation::Equal,
+                    Box::new(Node::Variable("luke".to_string())),
+                )],
+            ),
+            Node::Print(Box::new(Node::Variable("midichlorian".to_string()))),
+        ])];
+
+        let result = evaluate(ast, input.lock(), &mut output, &Options::default());
+        assert!(result.is_ok());
+
+        let output = String::from_utf8(output).expect("Not UTF-8");
+        assert_eq!(
+            output,
+            "That's impossible!That's impossible!From a certain point of view."
+        );
+    }
+
+    #[test]
+    fn logic() {
+        let input = io::stdin();
+        let mut output = Vec::new();
+        let ast = vec![Node::Main(vec![
+            Node::DeclareBoolean("lightside".to_string(), Box::new(Node::Boolean(true))),
+            Node::DeclareBoolean("darkside".to_string(), Box::new(Node::Boolean(false))),
+            Node::DeclareBoolean("revan".to_string(), Box::new(Node::Boolean(false))),
+            Node::AssignVariable(
+                "revan".to_string(),
+                Box::new(Node::Variable("lightside".to_string())),
+                vec![Node::Binary(
+                    BinaryOperation::Or,
+                    Box::new(Node::Variable("darkside".to_string())),
+                )],
+            ),
+            Node::Print(Box::new(Node::Variable("revan".to_string()))),
+            Node::AssignVariable(
+                "revan".to_string(),
+                Box::new(Node::Variable("revan".to_string())),
+                vec![Node::Binary(
+                    BinaryOperation::And,
+                    Box::new(Node::Variable("lightside".to_string())),
+                )],
+            ),
+            Node::Print(Box::new(Node::Variable("revan".to_string()))),
+            Node::AssignVariable(
+                "revan".to_string(),
+                Box::new(Node::Variable("revan".to_string())),
+                vec![Node::Unary(UnaryOperation::Not)],
+            ),
+            Node::Print(Box::new(Node::Variable("revan".to_string()))),
+        ])];
+
+        let result = evaluate(ast, input.lock(), &mut output, &Options::default());
+        assert!(result.is_ok());
+
+        let output = String::from_utf8(output).expect("Not UTF-8");
+        assert_eq!(
+            output,
+            "From a certain point of view.From a certain point of view.That's impossible!"
+        );
+    }
+
+    #[test]
+    fn while_loop() {
+        let input = io::stdin();
+        let mut output = Vec::new();
+        let ast = vec![Node::Main(vec![
+            Node::DeclareFloat("deathStars".to_string(), Box::new(Node::Float(3.0))),
+            Node::While(
+                Box::new(Node::Variable("deathStars".to_string())),
+                vec![
+                    Node::Print(Box::new(Node::Variable("deathStars".to_string()))),
+                    Node::AssignVariable(
+                        "deathStars".to_string(),
+                        Box::new(Node::Variable("deathStars".to_string())),
+                        vec![Node::Binary(
+                            BinaryOperation::Subtract,
+                            Box::new(Node::Float(1.0)),
+                        )],
+                    ),
+                ],
+            ),
+        ])];
+
+        let result = evaluate(ast, input.lock(), &mut output, &Options::default());
+        assert!(result.is_ok());
+
+        let output = String::from_utf8(output).expect("Not UTF-8");
+        assert_eq!(output, "321");
+    }
+
+    #[test]
+    fn for_loop() {
+        let input = io::stdin();
+        let mut output = Vec::new();
+        let ast = vec![Node::Main(vec![
+            Node::DeclareFloat("deadYounglings".to_string(), Box::new(Node::Float(0.0))),
+            Node::For(
+                Box::new(Node::Float(10.0)),
+                Box::new(Node::Variable("deadYounglings".to_string())),
+                vec![Node::Print(Box::new(Node::Variable(
+                    "deadYounglings".to_string(),
+                )))],
+            ),
+        ])];
+
+        let result = evaluate(ast, input.lock(), &mut output, &Options::default());
+        assert!(result.is_ok());
+
+        let output = String::from_utf8(output).expect("Not UTF-8");
+        assert_eq!(output, "0123456789");
+
+        // For loop with variable
+        let mut output = Vec::new();
+        let ast = vec![Node::Main(vec![
+            Node::DeclareFloat("deadYounglings".to_string(), Box::new(Node::Float(0.0))),
+            Node::DeclareFloat("lightsaberSwings".to_string(), Box::new(Node::Float(10.0))),
+            Node::For(
+                Box::new(Node::Variable("lightsaberSwings".to_string())),
+                Box::new(Node::Variable("deadYounglings".to_string())),
+                vec![Node::Print(Box::new(Node::Variable(
+                    "deadYounglings".to_string(),
+                )))],
+            ),
+        ])];
+
+        let result = evaluate(ast, input.lock(), &mut output, &Options::default());
+        assert!(result.is_ok());
+
+        let output = String::from_utf8(output).expect("Not UTF-8");
+        assert_eq!(output, "0123456789");
+    }
+
+    #[test]
+    fn loop_control() {
+        let input = io::stdin();
+        let mut output = Vec::new();
+        let ast = vec![Node::Main(vec![
+            Node::DeclareFloat("wing".to_string(), Box::new(Node::Float(0.0))),
+            Node::DeclareFloat("pilot".to_string(), Box::new(Node::Float(0.0))),
+            Node::DeclareBoolean("odd".to_string(), Box::new(Node::Boolean(false))),
+            Node::DeclareBoolean("done".to_string(), Box::new(Node::Boolean(false))),
+            Node::For(
+                Box::new(Node::Float(3.0)),
+                Box::new(Node::Variable("wing".to_string())),
+                vec![
+                    Node::AssignVariable("pilot".to_string(), Box::new(Node::Float(0.0)), vec![]),
+                    // Inner break only leaves the inner loop
+                    Node::For(
+                        Box::new(Node::Float(10.0)),
+                        Box::new(Node::Variable("pilot".to_string())),
+                        vec![
+                            Node::AssignVariable(
+                                "done".to_string(),
+                                Box::new(Node::Variable("pilot".to_string())),
+                                vec![Node::Binary(
+                                    BinaryOperation::GreaterThan,
+                                    Box::new(Node::Float(3.0)),
+                                )],
+                            ),
+                            Node::If(
+                                Box::new(Node::Variable("done".to_string())),
+                                vec![Node::Break],
+                                vec![],
+                            ),
+                            // Continue still moves the for loop along
+                            Node::AssignVariable(
+                                "odd".to_string(),
+                                Box::new(Node::Variable("pilot".to_string())),
+                                vec![
+                                    Node::Binary(
+                                        BinaryOperation::Modulus,
+                                        Box::new(Node::Float(2.0)),
+                                    ),
+                                    Node::Binary(
+                                        BinaryOperation::Equal,
+                                        Box::new(Node::Float(1.0)),
+                                    ),
+                                ],
+                            ),
+                            Node::If(
+                                Box::new(Node::Variable("odd".to_string())),
+                                vec![Node::Continue],
+                                vec![],
+                            ),
+                            Node::Print(Box::new(Node::Variable("pilot".to_string()))),
+                        ],
+                    ),
+                    Node::Print(Box::new(Node::String(",".to_string()))),
+                ],
+            ),
+        ])];
+
+        let result = evaluate(ast, input.lock(), &mut output, &Options::default());
+        assert_eq!(result, Ok(0));
+
+        let output = String::from_utf8(output).expect("Not UTF-8");
+        assert_eq!(output, "02,02,02,");
+
+        let input = io::stdin();
+        let mut output = Vec::new();
+        let ast = vec![Node::Main(vec![
+            Node::DeclareBoolean("flying".to_string(), Box::new(Node::Boolean(true))),
+            Node::While(
+                Box::new(Node::Variable("flying".to_string())),
+                vec![
+                    Node::Print(Box::new(Node::String("Punch it!".to_string()))),
+                    Node::Break,
+                ],
+            ),
+        ])];
+
+        let result = evaluate(ast, input.lock(), &mut output, &Options::default());
+        assert_eq!(result, Ok(0));
+
+        let output = String::from_utf8(output).expect("Not UTF-8");
+        assert_eq!(output, "Punch it!");
+    }
+
+    #[test]
+    fn lists() {
+        let input = io::stdin();
+        let mut output = Vec::new();
+        let ast = vec![Node::Main(vec![
+            Node::DeclareList("clones".to_string()),
+            Node::Append("clones".to_string(), Box::new(Node::Float(99.0))),
+            Node::Append("clones".to_string(), Box::new(Node::Float(1010.0))),
+            Node::SetElement(
+                "clones".to_string(),
+                Box::new(Node::Float(1.0)),
+                Box::new(Node::Float(5555.0)),
+            ),
+            Node::Print(Box::new(Node::Variable("clones".to_string()))),
+            Node::Print(Box::new(Node::Expression(
+                Box::new(Node::Variable("clones".to_string())),
+                vec![Node::Unary(UnaryOperation::Length)],
+            ))),
+            Node::Print(Box::new(Node::Expression(
+                Box::new(Node::Variable("clones".to_string())),
+                vec![Node::Binary(
+                    BinaryOperation::Index,
+                    Box::new(Node::Float(0.0)),
+                )],
+            ))),
+            Node::ForEach(
+                Box::new(Node::Variable("clones".to_string())),
+                Box::new(Node::Variable("clone".to_string())),
+                vec![Node::Print(Box::new(Node::Variable("clone".to_string())))],
+            ),
+        ])];
+
+        let result = evaluate(ast, input.lock(), &mut output, &Options::default());
+        assert!(result.is_ok());
+
+        let output = String::from_utf8(output).expect("Not UTF-8");
+        assert_eq!(output, "[99, 5555]299995555");
+
+        // Elements keep the type of the first one
+        let input = io::stdin();
+        let mut output = Vec::new();
+        let ast = vec![Node::Main(vec![
+            Node::DeclareList("clones".to_string()),
+            Node::Append("clones".to_string(), Box::new(Node::Float(99.0))),
+            Node::Append(
+                "clones".to_string(),
+                Box::new(Node::String("Fives".to_string())),
+            ),
+        ])];
+
+        let result = evaluate(ast, input.lock(), &mut output, &Options::default());
+        assert!(matches!(result, Err(Error::Type(_))));
+
+        let input = io::stdin();
+        let mut output = Vec::new();
+        let ast = vec![Node::Main(vec![
+            Node::DeclareList("clones".to_string()),
+            Node::SetElement(
+                "clones".to_string(),
+                Box::new(Node::Float(0.0)),
+                Box::new(Node::Float(99.0)),
+            ),
+        ])];
+
+        let result = evaluate(ast, input.lock(), &mut output, &Options::default());
+        assert!(matches!(result, Err(Error::Runtime(_))));
+    }
+
+    #[test]
+    fn maps() {
+        let input = io::stdin();
+        let mut output = Vec::new();
+        let ast = vec![Node::Main(vec![
+            Node::DeclareMap("planets".to_string()),
+            Node::SetElement(
+                "planets".to_string(),
+                Box::new(Node::String("Tatooine".to_string())),
+                Box::new(Node::Float(2.0)),
+            ),
+            Node::SetElement(
+                "planets".to_string(),
+                Box::new(Node::String("Alderaan".to_string())),
+                Box::new(Node::Float(1.0)),
+            ),
+            Node::SetElement(
+                "planets".to_string(),
+                Box::new(Node::String("Kamino".to_string())),
+                Box::new(Node::Float(1.0)),
+            ),
+            Node::Remove(
+                "planets".to_string(),
+                Box::new(Node::String("Kamino".to_string())),
+            ),
+            // Printed in key order
+            Node::Print(Box::new(Node::Variable("planets".to_string()))),
+            Node::Print(Box::new(Node::Expression(
+                Box::new(Node::Variable("planets".to_string())),
+                vec![Node::Binary(
+                    BinaryOperation::Index,
+                    Box::new(Node::String("Tatooine".to_string())),
+                )],
+            ))),
+            Node::Print(Box::new(Node::Expression(
+                Box::new(Node::Variable("planets".to_string())),
+                vec![Node::Binary(
+                    BinaryOperation::ContainsKey,
+                    Box::new(Node::String("Kamino".to_string())),
+                )],
+            ))),
+            Node::ForEach(
+                Box::new(Node::Variable("planets".to_string())),
+                Box::new(Node::Variable("planet".to_string())),
+                vec![Node::Print(Box::new(Node::Variable("planet".to_string())))],
+            ),
+        ])];
+
+        let result = evaluate(ast, input.lock(), &mut output, &Options::default());
+        assert!(result.is_ok());
+
+        let output = String::from_utf8(output).expect("Not UTF-8");
+        assert_eq!(
+            output,
+            "{Alderaan: 1, Tatooine: 2}2That's impossible!AlderaanTatooine"
+        );
+
+        let input = io::stdin();
+        let mut output = Vec::new();
+        let ast = vec![Node::Main(vec![
+            Node::DeclareMap("planets".to_string()),
+            Node::Print(Box::new(Node::Expression(
+                Box::new(Node::Variable("planets".to_string())),
+                vec![Node::Binary(
+                    BinaryOperation::Index,
+                    Box::new(Node::String("Kamino".to_string())),
+                )],
+            ))),
+        ])];
+
+        let result = evaluate(ast, input.lock(), &mut output, &Options::default());
+        assert!(matches!(result, Err(Error::Runtime(_))));
+    }
+
+    #[test]
+    fn records() {
+        let ship = Node::DeclareRecord(
+            "Ship".to_string(),
+            vec![
+                Node::DeclareString(
+                    "name".to_string(),
+                    Box::new(Node::String("Falcon".to_string())),
+                ),
+                Node::DeclareFloat("speed".to_string(), Box::new(Node::Float(1.5))),
+            ],
+        );
+
+        let input = io::stdin();
+        let mut output = Vec::new();
+        let ast = vec![
+            ship.clone(),
+            Node::DeclareFunction(
+                "Speed".to_string(),
+                vec![Node::Variable("ship".to_string())],
+                vec![Node::Return(Box::new(Node::Expression(
+                    Box::new(Node::Variable("ship".to_string())),
+                    vec![Node::Binary(
+                        BinaryOperation::Field,
+                        Box::new(Node::String("speed".to_string())),
+                    )],
+                )))],
+                false,
+                None,
+            ),
+            Node::Main(vec![
+                Node::NewRecord("Ship".to_string(), "falcon".to_string()),
+                Node::SetField(
+                    "falcon".to_string(),
+                    "speed".to_string(),
+                    Box::new(Node::Float(0.5)),
+                ),
+                Node::Print(Box::new(Node::Variable("falcon".to_string()))),
+                Node::DeclareFloat("speed".to_string(), Box::new(Node::Float(0.0))),
+                Node::AssignVariable(
+                    "speed".to_string(),
+                    Box::new(Node::CallFunction(
+                        "Speed".to_string(),
+                        vec![Node::Variable("falcon".to_string())],
+                    )),
+                    vec![],
+                ),
+                Node::Print(Box::new(Node::Variable("speed".to_string()))),
+            ]),
+        ];
+
+        let result = evaluate(ast, input.lock(), &mut output, &Options::default());
+        assert!(result.is_ok());
+
+        let output = String::from_utf8(output).expect("Not UTF-8");
+        assert_eq!(output, "Ship { name: Falcon, speed: 0.5 }0.5");
+
+        // Fields keep their type
+        let input = io::stdin();
+        let mut output = Vec::new();
+        let ast = vec![
+            ship,
+            Node::Main(vec![
+                Node::NewRecord("Ship".to_string(), "falcon".to_string()),
+                Node::SetField(
+                    "falcon".to_string(),
+                    "speed".to_string(),
+                    Box::new(Node::String("Fast".to_string())),
+                ),
+            ]),
+        ];
+
+        let result = evaluate(ast, input.lock(), &mut output, &Options::default());
+        assert!(matches!(result, Err(Error::Type(_))));
+    }
+
+    #[test]
+    fn if_else() {
+        let input = io::stdin();
+        let mut output = Vec::new();
+        let ast = vec![Node::Main(vec![Node::If(
+            Box::new(Node::Boolean(true)),
             vec![Node::Print(Box::new(Node::String("Do".to_string())))],
             vec![Node::Print(Box::new(Node::String("Don't".to_string())))],
         )])];
 
-        let result = evaluate(ast, input.lock(), &mut output);
+        let result = evaluate(ast, input.lock(), &mut output, &Options::default());
+        assert!(result.is_ok());
+
+        let output = String::from_utf8(output).expect("Not UTF-8");
+        assert_eq!(output, "Do");
+    }
+
+    #[test]
+    fn functions() {
+        let input = io::stdin();
+        let mut output = Vec::new();
+        let ast = vec![
+            Node::DeclareFunction(
+                "NameTheSystem".to_string(),
+                vec![Node::Variable("planet".to_string())],
+                vec![
+                    Node::Print(Box::new(Node::String("Goodbye".to_string()))),
+                    Node::Print(Box::new(Node::Variable("planet".to_string()))),
+                    Node::Print(Box::new(Node::String("Deathstar noise".to_string()))),
+                ],
+                true,
+                None,
+            ),
+            Node::Main(vec![Node::CallFunction(
+                "NameTheSystem".to_string(),
+                vec![Node::String("Alderaan".to_string())],
+            )]),
+        ];
+
+        let result = evaluate(ast, input.lock(), &mut output, &Options::default());
+        assert!(result.is_ok());
+
+        let output = String::from_utf8(output).expect("Not UTF-8");
+        assert_eq!(output, "GoodbyeAlderaanDeathstar noise");
+
+        let input = io::stdin();
+        let mut output = Vec::new();
+        let ast = vec![
+            Node::DeclareFunction(
+                "TheOdds".to_string(),
+                vec![Node::Variable("odds".to_string())],
+                vec![
+                    Node::DeclareBoolean("survive".to_string(), Box::new(Node::Boolean(false))),
+                    Node::AssignVariable(
+                        "survive".to_string(),
+                        Box::new(Node::Variable("odds".to_string())),
+                        vec![
+                            Node::Binary(BinaryOperation::Modulus, Box::new(Node::Float(3720.0))),
+                            Node::Binary(BinaryOperation::Equal, Box::new(Node::Float(0.0))),
+                        ],
+                    ),
+                    Node::Return(Box::new(Node::Variable("survive".to_string()))),
+                ],
+                false,
+                None,
+            ),
+            Node::Main(vec![
+                Node::DeclareBoolean("survive".to_string(), Box::new(Node::Boolean(false))),
+                Node::AssignVariable(
+                    "survive".to_string(),
+                    Box::new(Node::CallFunction(
+                        "TheOdds".to_string(),
+                        vec![Node::Float(52.0)],
+                    )),
+                    vec![],
+                ),
+                Node::Print(Box::new(Node::Variable("survive".to_string()))),
+            ]),
+        ];
+
+        let result = evaluate(ast, input.lock(), &mut output, &Options::default());
+        assert!(result.is_ok());
+
+        let output = String::from_utf8(output).expect("Not UTF-8");
+        assert_eq!(output, "That's impossible!");
+    }
+
+    #[test]
+    fn function_values() {
+        let input = io::stdin();
+        let mut output = Vec::new();
+        let ast = vec![
+            Node::DeclareFunction(
+                "Double".to_string(),
+                vec![Node::Variable("x".to_string())],
+                vec![Node::Return(Box::new(Node::Expression(
+                    Box::new(Node::Variable("x".to_string())),
+                    vec![Node::Binary(
+                        BinaryOperation::Multiply,
+                        Box::new(Node::Integer(2)),
+                    )],
+                )))],
+                false,
+                None,
+            ),
+            Node::DeclareFunction(
+                "Apply".to_string(),
+                vec![
+                    Node::Variable("f".to_string()),
+                    Node::Variable("x".to_string()),
+                ],
+                vec![Node::Return(Box::new(Node::CallFunction(
+                    "f".to_string(),
+                    vec![Node::Variable("x".to_string())],
+                )))],
+                false,
+                None,
+            ),
+            Node::Main(vec![
+                Node::DeclareFunctionVariable(
+                    "op".to_string(),
+                    Box::new(Node::Function("Double".to_string())),
+                ),
+                Node::DeclareInteger(
+                    "x".to_string(),
+                    Box::new(Node::CallFunction("op".to_string(), vec![Node::Integer(4)])),
+                ),
+                Node::DeclareInteger(
+                    "y".to_string(),
+                    Box::new(Node::CallFunction(
+                        "Apply".to_string(),
+                        vec![Node::Function("Double".to_string()), Node::Integer(5)],
+                    )),
+                ),
+                Node::Print(Box::new(Node::Variable("x".to_string()))),
+                Node::Print(Box::new(Node::Variable("y".to_string()))),
+                Node::Print(Box::new(Node::Function("Double".to_string()))),
+            ]),
+        ];
+
+        let result = evaluate(ast, input.lock(), &mut output, &Options::default());
+        assert!(result.is_ok());
+
+        let output = String::from_utf8(output).expect("Not UTF-8");
+        assert_eq!(output, "810Double");
+
+        // A function variable can be set to another function
+        let input = io::stdin();
+        let mut output = Vec::new();
+        let ast = vec![
+            Node::DeclareFunction(
+                "Luke".to_string(),
+                vec![],
+                vec![Node::Print(Box::new(Node::String("Luke".to_string())))],
+                true,
+                None,
+            ),
+            Node::DeclareFunction(
+                "Leia".to_string(),
+                vec![],
+                vec![Node::Print(Box::new(Node::String("Leia".to_string())))],
+                true,
+                None,
+            ),
+            Node::Main(vec![
+                Node::DeclareFunctionVariable(
+                    "twin".to_string(),
+                    Box::new(Node::Function("Luke".to_string())),
+                ),
+                Node::CallFunction("twin".to_string(), vec![]),
+                Node::AssignVariable(
+                    "twin".to_string(),
+                    Box::new(Node::Function("Leia".to_string())),
+                    vec![],
+                ),
+                Node::CallFunction("twin".to_string(), vec![]),
+            ]),
+        ];
+
+        let result = evaluate(ast, input.lock(), &mut output, &Options::default());
+        assert!(result.is_ok());
+
+        let output = String::from_utf8(output).expect("Not UTF-8");
+        assert_eq!(output, "LukeLeia");
+
+        // Only declared functions can be used as values
+        let input = io::stdin();
+        let mut output = Vec::new();
+        let ast = vec![Node::Main(vec![Node::DeclareFunctionVariable(
+            "op".to_string(),
+            Box::new(Node::Function("Missing".to_string())),
+        )])];
+
+        let result = evaluate(ast, input.lock(), &mut output, &Options::default());
+        assert!(matches!(result, Err(Error::Runtime(_))));
+
+        let input = io::stdin();
+        let mut output = Vec::new();
+        let ast = vec![Node::Main(vec![Node::DeclareFunctionVariable(
+            "op".to_string(),
+            Box::new(Node::Integer(2)),
+        )])];
+
+        let result = evaluate(ast, input.lock(), &mut output, &Options::default());
+        assert!(matches!(result, Err(Error::Type(_))));
+    }
+
+    #[test]
+    fn closures() {
+        let input = io::stdin();
+        let mut output = Vec::new();
+        let ast = vec![
+            Node::DeclareFunction(
+                "MakeAdder".to_string(),
+                vec![Node::Variable("n".to_string())],
+                vec![Node::Return(Box::new(Node::AnonymousFunction(
+                    vec![Node::Variable("x".to_string())],
+                    vec![Node::Return(Box::new(Node::Expression(
+                        Box::new(Node::Variable("x".to_string())),
+                        vec![Node::Binary(
+                            BinaryOperation::Add,
+                            Box::new(Node::Variable("n".to_string())),
+                        )],
+                    )))],
+                    false,
+                    None,
+                )))],
+                false,
+                None,
+            ),
+            Node::Main(vec![
+                Node::DeclareFunctionVariable(
+                    "addFive".to_string(),
+                    Box::new(Node::CallFunction(
+                        "MakeAdder".to_string(),
+                        vec![Node::Integer(5)],
+                    )),
+                ),
+                Node::DeclareInteger(
+                    "sum".to_string(),
+                    Box::new(Node::CallFunction(
+                        "addFive".to_string(),
+                        vec![Node::Integer(10)],
+                    )),
+                ),
+                Node::Print(Box::new(Node::Variable("sum".to_string()))),
+                // Variables are captured by value when the function is made
+                Node::DeclareInteger("count".to_string(), Box::new(Node::Integer(1))),
+                Node::DeclareFunctionVariable(
+                    "show".to_string(),
+                    Box::new(Node::AnonymousFunction(
+                        vec![],
+                        vec![Node::Print(Box::new(Node::Variable("count".to_string())))],
+                        true,
+                        None,
+                    )),
+                ),
+                Node::AssignVariable("count".to_string(), Box::new(Node::Integer(2)), vec![]),
+                Node::CallFunction("show".to_string(), vec![]),
+                Node::Print(Box::new(Node::Variable("count".to_string()))),
+                // An existing variable can be set to a new anonymous function
+                Node::AssignVariable(
+                    "show".to_string(),
+                    Box::new(Node::AnonymousFunction(
+                        vec![],
+                        vec![Node::Print(Box::new(Node::Variable("count".to_string())))],
+                        true,
+                        None,
+                    )),
+                    vec![],
+                ),
+                Node::CallFunction("show".to_string(), vec![]),
+            ]),
+        ];
+
+        let result = evaluate(ast, input.lock(), &mut output, &Options::default());
         assert!(result.is_ok());
 
         let output = String::from_utf8(output).expect("Not UTF-8");
-        assert_eq!(output, "Do");
+        assert_eq!(output, "15122");
+
+        let input = io::stdin();
+        let mut output = Vec::new();
+        let ast = vec![Node::Main(vec![
+            Node::DeclareInteger("count".to_string(), Box::new(Node::Integer(1))),
+            Node::DeclareFunctionVariable(
+                "bump".to_string(),
+                Box::new(Node::AnonymousFunction(
+                    vec![],
+                    vec![Node::AssignVariable(
+                        "count".to_string(),
+                        Box::new(Node::Integer(2)),
+                        vec![],
+                    )],
+                    true,
+                    None,
+                )),
+            ),
+            Node::CallFunction("bump".to_string(), vec![]),
+        ])];
+
+        let result = evaluate(ast, input.lock(), &mut output, &Options::default());
+        assert!(matches!(result, Err(Error::Runtime(_))));
     }
 
     #[test]
-    fn functions() {
+    fn typed_functions() {
+        let half = Node::DeclareFunction(
+            "Half".to_string(),
+            vec![Node::TypedParameter("x".to_string(), Type::Float)],
+            vec![Node::Return(Box::new(Node::Expression(
+                Box::new(Node::Variable("x".to_string())),
+                vec![Node::Binary(
+                    BinaryOperation::Divide,
+                    Box::new(Node::Integer(2)),
+                )],
+            )))],
+            false,
+            Some(Type::Float),
+        );
+
+        // Integers widen into floats for a parameter or return value
         let input = io::stdin();
         let mut output = Vec::new();
         let ast = vec![
+            half.clone(),
             Node::DeclareFunction(
-                "NameTheSystem".to_string(),
-                vec![Node::Variable("planet".to_string())],
-                vec![
-                    Node::Print(Box::new(Node::String("Goodbye".to_string()))),
-                    Node::Print(Box::new(Node::Variable("planet".to_string()))),
-                    Node::Print(Box::new(Node::String("Deathstar noise".to_string()))),
-                ],
-                true,
+                "Count".to_string(),
+                vec![Node::TypedParameter("items".to_string(), Type::List)],
+                vec![Node::Return(Box::new(Node::Integer(3)))],
+                false,
+                Some(Type::Float),
             ),
-            Node::Main(vec![Node::CallFunction(
-                "NameTheSystem".to_string(),
-                vec![Node::String("Alderaan".to_string())],
-            )]),
+            Node::Main(vec![
+                Node::DeclareFloat(
+                    "half".to_string(),
+                    Box::new(Node::CallFunction(
+                        "Half".to_string(),
+                        vec![Node::Integer(3)],
+                    )),
+                ),
+                Node::DeclareList("items".to_string()),
+                Node::DeclareFloat(
+                    "count".to_string(),
+                    Box::new(Node::CallFunction(
+                        "Count".to_string(),
+                        vec![Node::Variable("items".to_string())],
+                    )),
+                ),
+                Node::Print(Box::new(Node::Variable("half".to_string()))),
+                Node::Print(Box::new(Node::Variable("count".to_string()))),
+            ]),
         ];
 
-        let result = evaluate(ast, input.lock(), &mut output);
+        let result = evaluate(ast, input.lock(), &mut output, &Options::default());
         assert!(result.is_ok());
 
         let output = String::from_utf8(output).expect("Not UTF-8");
-        assert_eq!(output, "GoodbyeAlderaanDeathstar noise");
+        assert_eq!(output, "1.53");
+
+        // Errors name the function and parameter
+        let input = io::stdin();
+        let mut output = Vec::new();
+        let ast = vec![
+            half,
+            Node::Main(vec![Node::CallFunction(
+                "Half".to_string(),
+                vec![Node::String("two".to_string())],
+            )]),
+        ];
+
+        let result = evaluate(ast, input.lock(), &mut output, &Options::default());
+        assert_eq!(
+            result,
+            Err(Error::Type(
+                "Half expected x to be of type float, not string".to_string()
+            ))
+        );
+
+        let input = io::stdin();
+        let mut output = Vec::new();
+        let ast = vec![Node::Main(vec![
+            Node::DeclareFunctionVariable(
+                "name".to_string(),
+                Box::new(Node::AnonymousFunction(
+                    vec![],
+                    vec![Node::Return(Box::new(Node::Integer(2)))],
+                    false,
+                    Some(Type::String),
+                )),
+            ),
+            Node::CallFunction("name".to_string(), vec![]),
+        ])];
+
+        let result = evaluate(ast, input.lock(), &mut output, &Options::default());
+        assert_eq!(
+            result,
+            Err(Error::Type(
+                "name must return a value of type string, not integer".to_string()
+            ))
+        );
+    }
 
+    #[test]
+    fn early_return() {
         let input = io::stdin();
         let mut output = Vec::new();
         let ast = vec![
             Node::DeclareFunction(
-                "TheOdds".to_string(),
-                vec![Node::Variable("odds".to_string())],
+                "FindTheRebels".to_string(),
+                vec![Node::Variable("system".to_string())],
                 vec![
-                    Node::DeclareBoolean("survive".to_string(), Box::new(Node::Boolean(false))),
-                    Node::AssignVariable(
-                        "survive".to_string(),
-                        Box::new(Node::Variable("odds".to_string())),
+                    Node::DeclareFloat("searched".to_string(), Box::new(Node::Float(0.0))),
+                    Node::DeclareBoolean("found".to_string(), Box::new(Node::Boolean(false))),
+                    Node::DeclareBoolean("searching".to_string(), Box::new(Node::Boolean(true))),
+                    Node::While(
+                        Box::new(Node::Variable("searching".to_string())),
                         vec![
-                            Node::Binary(BinaryOperation::Modulus, Box::new(Node::Float(3720.0))),
-                            Node::Binary(BinaryOperation::Equal, Box::new(Node::Float(0.0))),
+                            Node::AssignVariable(
+                                "searched".to_string(),
+                                Box::new(Node::Variable("searched".to_string())),
+                                vec![Node::Binary(
+                                    BinaryOperation::Add,
+                                    Box::new(Node::Float(1.0)),
+                                )],
+                            ),
+                            Node::AssignVariable(
+                                "found".to_string(),
+                                Box::new(Node::Variable("searched".to_string())),
+                                vec![Node::Binary(
+                                    BinaryOperation::GreaterThan,
+                                    Box::new(Node::Float(2.0)),
+                                )],
+                            ),
+                            Node::If(
+                                Box::new(Node::Variable("found".to_string())),
+                                vec![Node::Return(Box::new(Node::Variable(
+                                    "searched".to_string(),
+                                )))],
+                                vec![],
+                            ),
                         ],
                     ),
-                    Node::Return(Box::new(Node::Variable("survive".to_string()))),
+                    Node::Return(Box::new(Node::Float(0.0))),
                 ],
                 false,
+                None,
+            ),
+            Node::DeclareFunction(
+                "Evacuate".to_string(),
+                vec![],
+                vec![
+                    Node::DeclareFloat("transports".to_string(), Box::new(Node::Float(0.0))),
+                    Node::For(
+                        Box::new(Node::Float(10.0)),
+                        Box::new(Node::Variable("transports".to_string())),
+                        vec![
+                            Node::Print(Box::new(Node::Variable("transports".to_string()))),
+                            Node::Return(Box::new(Node::Noop)),
+                        ],
+                    ),
+                    Node::Print(Box::new(Node::String("Never printed".to_string()))),
+                ],
+                true,
+                None,
             ),
             Node::Main(vec![
-                Node::DeclareBoolean("survive".to_string(), Box::new(Node::Boolean(false))),
+                Node::DeclareFloat("found".to_string(), Box::new(Node::Float(0.0))),
                 Node::AssignVariable(
-                    "survive".to_string(),
+                    "found".to_string(),
                     Box::new(Node::CallFunction(
-                        "TheOdds".to_string(),
-                        vec![Node::Float(52.0)],
+                        "FindTheRebels".to_string(),
+                        vec![Node::String("Hoth".to_string())],
                     )),
                     vec![],
                 ),
-                Node::Print(Box::new(Node::Variable("survive".to_string()))),
+                Node::Print(Box::new(Node::Variable("found".to_string()))),
+                Node::CallFunction("Evacuate".to_string(), vec![]),
+                Node::Print(Box::new(Node::String("Hoth".to_string()))),
             ]),
         ];
 
-        let result = evaluate(ast, input.lock(), &mut output);
-        assert!(result.is_ok());
+        let result = evaluate(ast, input.lock(), &mut output, &Options::default());
+        assert_eq!(result, Ok(0));
 
         let output = String::from_utf8(output).expect("Not UTF-8");
-        assert_eq!(output, "That's impossible!");
+        assert_eq!(output, "30Hoth");
+
+        // Void functions cannot return a value
+        let input = io::stdin();
+        let mut output = Vec::new();
+        let ast = vec![
+            Node::DeclareFunction(
+                "Evacuate".to_string(),
+                vec![],
+                vec![Node::Return(Box::new(Node::Float(1.0)))],
+                true,
+                None,
+            ),
+            Node::Main(vec![Node::CallFunction("Evacuate".to_string(), vec![])]),
+        ];
+
+        let result = evaluate(ast, input.lock(), &mut output, &Options::default());
+        assert!(matches!(result, Err(Error::Type(_))));
     }
 
     #[test]
@@ -965,7 +3750,7 @@ mod tests {
             Node::Print(Box::new(Node::Variable("jawa".to_string()))),
         ])];
 
-        let result = evaluate(ast, input.as_bytes(), &mut output);
+        let result = evaluate(ast, input.as_bytes(), &mut output, &Options::default());
         assert!(result.is_ok());
 
         let output = String::from_utf8(output).expect("Not UTF-8");
@@ -979,7 +3764,7 @@ mod tests {
             Node::Print(Box::new(Node::Variable("ewok".to_string()))),
         ])];
 
-        let result = evaluate(ast, input.as_bytes(), &mut output);
+        let result = evaluate(ast, input.as_bytes(), &mut output, &Options::default());
         assert!(result.is_ok());
 
         let output = String::from_utf8(output).expect("Not UTF-8");
@@ -993,13 +3778,180 @@ mod tests {
             Node::Print(Box::new(Node::Variable("darkSide".to_string()))),
         ])];
 
-        let result = evaluate(ast, input.as_bytes(), &mut output);
+        let result = evaluate(ast, input.as_bytes(), &mut output, &Options::default());
         assert!(result.is_ok());
 
         let output = String::from_utf8(output).expect("Not UTF-8");
         assert_eq!(output, "That's impossible!");
     }
 
+    #[test]
+    fn assert() {
+        let input = io::stdin();
+        let mut output = Vec::new();
+        let ast = vec![Node::Main(vec![
+            Node::Assert(
+                Box::new(Node::Boolean(true)),
+                "From a certain point of view.".to_string(),
+            ),
+            Node::Print(Box::new(Node::String("Faith".to_string()))),
+        ])];
+
+        let result = evaluate(ast, input.lock(), &mut output, &Options::default());
+        assert!(result.is_ok());
+
+        let output = String::from_utf8(output).expect("Not UTF-8");
+        assert_eq!(output, "Faith");
+
+        let input = io::stdin();
+        let mut output = Vec::new();
+        let ast = vec![Node::Main(vec![
+            Node::DeclareBoolean("faith".to_string(), Box::new(Node::Boolean(false))),
+            Node::Assert(
+                Box::new(Node::Variable("faith".to_string())),
+                "faith".to_string(),
+            ),
+            Node::Print(Box::new(Node::String("Faith".to_string()))),
+        ])];
+
+        let result = evaluate(ast, input.lock(), &mut output, &Options::default());
+        assert_eq!(
+            result,
+            Err(Error::Assertion(
+                "I find your lack of faith disturbing. Assertion failed: faith".to_string()
+            ))
+        );
+
+        let output = String::from_utf8(output).expect("Not UTF-8");
+        assert_eq!(output, "");
+
+        // A failed assertion is not caught
+        let input = io::stdin();
+        let mut output = Vec::new();
+        let ast = vec![Node::Main(vec![Node::Try(
+            vec![Node::Assert(
+                Box::new(Node::Boolean(false)),
+                "That's impossible!".to_string(),
+            )],
+            None,
+            vec![Node::Print(Box::new(Node::String("Caught".to_string())))],
+        )])];
+
+        let result = evaluate(ast, input.lock(), &mut output, &Options::default());
+        assert!(matches!(result, Err(Error::Assertion(_))));
+
+        let output = String::from_utf8(output).expect("Not UTF-8");
+        assert_eq!(output, "");
+    }
+
+    #[test]
+    fn exit() {
+        let input = io::stdin();
+        let mut output = Vec::new();
+        let ast = vec![
+            Node::DeclareFunction(
+                "Duel".to_string(),
+                vec![],
+                vec![
+                    Node::Print(Box::new(Node::String("High ground".to_string()))),
+                    Node::Exit(Box::new(Node::Float(3.0))),
+                    Node::Print(Box::new(Node::String("Low ground".to_string()))),
+                ],
+                true,
+                None,
+            ),
+            Node::Main(vec![
+                Node::DeclareBoolean("fighting".to_string(), Box::new(Node::Boolean(true))),
+                Node::While(
+                    Box::new(Node::Variable("fighting".to_string())),
+                    vec![Node::CallFunction("Duel".to_string(), vec![])],
+                ),
+                Node::Print(Box::new(Node::String("Mustafar".to_string()))),
+            ]),
+        ];
+
+        let result = evaluate(ast, input.lock(), &mut output, &Options::default());
+        assert_eq!(result, Ok(3));
+
+        let output = String::from_utf8(output).expect("Not UTF-8");
+        assert_eq!(output, "High ground");
+
+        // Exiting while a variable's value is worked out stops before it is declared
+        let input = io::stdin();
+        let mut output = Vec::new();
+        let ast = vec![
+            Node::DeclareFunction(
+                "Surrender".to_string(),
+                vec![],
+                vec![
+                    Node::Exit(Box::new(Node::Integer(5))),
+                    Node::Return(Box::new(Node::Integer(1))),
+                ],
+                false,
+                None,
+            ),
+            Node::Main(vec![
+                Node::DeclareInteger(
+                    "troops".to_string(),
+                    Box::new(Node::CallFunction("Surrender".to_string(), vec![])),
+                ),
+                Node::Print(Box::new(Node::Variable("troops".to_string()))),
+            ]),
+        ];
+
+        let result = evaluate(ast, input.lock(), &mut output, &Options::default());
+        assert_eq!(result, Ok(5));
+
+        let output = String::from_utf8(output).expect("Not UTF-8");
+        assert_eq!(output, "");
+
+        let input = io::stdin();
+        let mut output = Vec::new();
+        let ast = vec![Node::Main(vec![Node::Exit(Box::new(Node::Float(0.5)))])];
+
+        let result = evaluate(ast, input.lock(), &mut output, &Options::default());
+        assert!(matches!(result, Err(Error::Type(_))));
+    }
+
+    #[test]
+    fn error_kinds() {
+        let input = io::stdin();
+        let mut output = Vec::new();
+        let ast = vec![Node::Main(vec![
+            Node::DeclareBoolean("jarjar".to_string(), Box::new(Node::Boolean(true))),
+            Node::AssignVariable(
+                "jarjar".to_string(),
+                Box::new(Node::Variable("jarjar".to_string())),
+                vec![Node::Binary(
+                    BinaryOperation::Add,
+                    Box::new(Node::Float(1.0)),
+                )],
+            ),
+        ])];
+
+        let result = evaluate(ast, input.lock(), &mut output, &Options::default());
+        assert!(matches!(result, Err(Error::Type(_))));
+
+        let input = io::stdin();
+        let mut output = Vec::new();
+        let ast = vec![Node::Main(vec![Node::Print(Box::new(Node::Variable(
+            "jarjar".to_string(),
+        )))])];
+
+        let result = evaluate(ast, input.lock(), &mut output, &Options::default());
+        assert!(matches!(result, Err(Error::Runtime(_))));
+
+        let input = "Meesa";
+        let mut output = Vec::new();
+        let ast = vec![Node::Main(vec![
+            Node::DeclareFloat("jarjar".to_string(), Box::new(Node::Float(0.0))),
+            Node::ReadFloat(Box::new(Node::Variable("jarjar".to_string()))),
+        ])];
+
+        let result = evaluate(ast, input.as_bytes(), &mut output, &Options::default());
+        assert!(matches!(result, Err(Error::Runtime(_))));
+    }
+
     #[test]
     fn type_change() {
         let input = io::stdin();
@@ -1016,7 +3968,7 @@ mod tests {
             ),
         ])];
 
-        let result = evaluate(ast, input.lock(), &mut output);
+        let result = evaluate(ast, input.lock(), &mut output, &Options::default());
         assert!(result.is_err());
 
         let input = io::stdin();
@@ -1026,7 +3978,7 @@ mod tests {
             Node::DeclareFloat("jarjar".to_string(), Box::new(Node::Float(1.0))),
         ])];
 
-        let result = evaluate(ast, input.lock(), &mut output);
+        let result = evaluate(ast, input.lock(), &mut output, &Options::default());
         assert!(result.is_err());
     }
 
@@ -1036,7 +3988,7 @@ mod tests {
         let mut output = Vec::new();
         let ast = vec![Node::Main(Vec::new())];
 
-        let result = evaluate(ast, input.lock(), &mut output);
+        let result = evaluate(ast, input.lock(), &mut output, &Options::default());
         assert!(result.is_ok());
 
         let output = String::from_utf8(output).expect("Not UTF-8");
@@ -1046,7 +3998,7 @@ mod tests {
         let mut output = Vec::new();
         let ast = vec![];
 
-        let result = evaluate(ast, input.lock(), &mut output);
+        let result = evaluate(ast, input.lock(), &mut output, &Options::default());
         assert!(result.is_ok());
 
         let output = String::from_utf8(output).expect("Not UTF-8");
@@ -0,0 +1,13 @@
+//! The Force, a Star Wars inspired programming language.
+//!
+//! Programs are parsed into an AST with [`parser::parse`], checked with [`checker::check`], then
+//! run with [`interpreter::evaluate`]. Rust functions can be given to programs by registering them
+//! in the [`interpreter::Natives`] passed in through [`interpreter::Options`].
+
+pub mod ast;
+mod builtins;
+pub mod checker;
+pub mod error;
+pub mod interpreter;
+pub mod parser;
+pub mod runner;
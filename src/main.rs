@@ -1,21 +1,40 @@
-use std::io;
+use std::{io, process};
 
-mod ast;
 mod cli;
-mod interpreter;
-mod parser;
 
 #[cfg(feature = "llvm")]
 mod compiler;
 
-fn main() -> Result<(), String> {
+use theforce::{checker, error::Error, interpreter, parser, runner};
+
+fn main() {
+    let status = match run() {
+        Ok(status) => status,
+        Err(e) => {
+            eprintln!("{}", e);
+            e.exit_code()
+        }
+    };
+    process::exit(status);
+}
+
+fn run() -> Result<i32, Error> {
     let args = cli::parse_arguments();
+    if let Some(test_args) = args.subcommand_matches("test") {
+        let options = cli::read_options(test_args);
+        return runner::run(test_args.value_of("PATH").unwrap(), &options).map(|_| 0);
+    }
+
+    let options = cli::read_options(&args);
     let source = cli::read_source(args)?;
 
     let ast = parser::parse(source.as_str());
 
     match ast {
-        Ok(t) => interpreter::evaluate(t, io::stdin().lock(), io::stdout()),
-        Err(e) => Err(format!("Error encountered while parsing: {}", e)),
+        Ok(t) => {
+            checker::check(&t)?;
+            interpreter::evaluate(t, io::stdin().lock(), io::stdout(), &options)
+        }
+        Err(e) => Err(Error::Parse(e.to_string())),
     }
 }
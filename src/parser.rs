@@ -1,9 +1,9 @@
 extern crate pest;
 extern crate pest_derive;
 
-use pest::Parser;
+use pest::{error::ErrorVariant, Parser};
 
-use crate::ast::{BinaryOperation, Node, UnaryOperation};
+use crate::ast::{BinaryOperation, Node, Type, UnaryOperation};
 
 #[derive(pest_derive::Parser)]
 #[grammar = "grammar.pest"]
@@ -12,6 +12,7 @@ struct ForceParser;
 pub fn parse(source: &str) -> Result<Vec<Node>, Box<pest::error::Error<Rule>>> {
     let mut ast = vec![];
     let pairs = ForceParser::parse(Rule::Program, source)?;
+    check_integers(pairs.clone())?;
     for pair in pairs {
         if let Rule::Functions = pair.as_rule() {
             for pair in pair.into_inner() {
@@ -22,6 +23,59 @@ pub fn parse(source: &str) -> Result<Vec<Node>, Box<pest::error::Error<Rule>>> {
     Ok(ast)
 }
 
+/// Errors on any hex or binary integer too big to fit, before the AST is built.
+fn check_integers(
+    pairs: pest::iterators::Pairs<Rule>,
+) -> Result<(), Box<pest::error::Error<Rule>>> {
+    for pair in pairs.flatten() {
+        let parsed = match pair.as_rule() {
+            Rule::HexInteger => parse_radix(pair.as_str(), "0x", 16),
+            Rule::BinaryInteger => parse_radix(pair.as_str(), "0b", 2),
+            _ => continue,
+        };
+        if parsed.is_err() {
+            return Err(Box::new(pest::error::Error::new_from_span(
+                ErrorVariant::CustomError {
+                    message: format!("{} is too big for an integer", pair.as_str()),
+                },
+                pair.as_span(),
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Parses an integer written with a prefix like 0x, keeping any sign in front of the prefix.
+fn parse_radix(literal: &str, prefix: &str, radix: u32) -> Result<i64, std::num::ParseIntError> {
+    let digits = literal.replacen(prefix, "", 1);
+    i64::from_str_radix(&digits, radix)
+}
+
+fn parse_roman(numeral: &str) -> i64 {
+    let values = numeral
+        .chars()
+        .map(|c| match c {
+            'M' => 1000,
+            'D' => 500,
+            'C' => 100,
+            'L' => 50,
+            'X' => 10,
+            'V' => 5,
+            _ => 1,
+        })
+        .collect::<Vec<i64>>();
+
+    // A smaller numeral before a larger one is taken away from it, as in IV
+    let mut total = 0;
+    for (i, value) in values.iter().enumerate() {
+        match values.get(i + 1) {
+            Some(next) if next > value => total -= value,
+            _ => total += value,
+        }
+    }
+    total
+}
+
 fn build_ast(pair: pest::iterators::Pair<Rule>) -> Node {
     match pair.as_rule() {
         Rule::Main => {
@@ -32,14 +86,25 @@ fn build_ast(pair: pest::iterators::Pair<Rule>) -> Node {
             }
             Node::Main(body)
         }
+        Rule::RecordDeclaration => {
+            let mut pairs = pair.into_inner();
+            let identifier = pairs.next().unwrap().as_str();
+            let mut fields = Vec::<Node>::new();
+            for pair in pairs {
+                fields.push(build_ast(pair));
+            }
+            Node::DeclareRecord(identifier.to_string(), fields)
+        }
         Rule::VoidFunction => build_function(pair, true),
         Rule::NonVoidFunction => build_function(pair, false),
-        Rule::CallFunctionStatement => {
+        Rule::VoidAnonymousFunction => build_anonymous_function(pair, true),
+        Rule::NonVoidAnonymousFunction => build_anonymous_function(pair, false),
+        Rule::CallFunctionStatement | Rule::CallFunctionOperand => {
             let mut pairs = pair.into_inner();
             let identifier = pairs.next().unwrap().as_str();
             let mut arguments = Vec::<Node>::new();
             let maybe_args = pairs.next().unwrap();
-            if Rule::Arguments == maybe_args.as_rule() {
+            if let Rule::Arguments | Rule::OperandArguments = maybe_args.as_rule() {
                 for pair in maybe_args.into_inner() {
                     arguments.push(build_ast(pair));
                 }
@@ -56,6 +121,34 @@ fn build_ast(pair: pest::iterators::Pair<Rule>) -> Node {
             }
             Node::AssignVariable(identifier.to_string(), Box::new(value), operations)
         }
+        Rule::Group => {
+            let mut pairs = pair.into_inner();
+            let value = build_ast(pairs.next().unwrap());
+            let mut operations = Vec::<Node>::new();
+            for pair in pairs {
+                operations.push(build_ast(pair));
+            }
+            Node::Expression(Box::new(value), operations)
+        }
+        Rule::Expression | Rule::InitialValue => {
+            let mut pairs = pair.into_inner();
+            let value = build_ast(pairs.next().unwrap());
+            let mut operations = Vec::<Node>::new();
+            for pair in pairs {
+                operations.push(build_ast(pair));
+            }
+
+            // Without any operations, it is only the value
+            if operations.is_empty() {
+                value
+            } else {
+                Node::Expression(Box::new(value), operations)
+            }
+        }
+        Rule::DeclareConstantStatement => {
+            let declaration = build_ast(pair.into_inner().next().unwrap());
+            Node::DeclareConstant(Box::new(declaration))
+        }
         Rule::DeclareBooleanStatement => {
             let mut pair = pair.into_inner();
             let identifier = pair.next().unwrap().as_str();
@@ -68,12 +161,66 @@ fn build_ast(pair: pest::iterators::Pair<Rule>) -> Node {
             let value = build_ast(pair.next().unwrap());
             Node::DeclareFloat(identifier.to_string(), Box::new(value))
         }
+        Rule::DeclareFunctionVariableStatement => {
+            let mut pair = pair.into_inner();
+            let identifier = pair.next().unwrap().as_str();
+            let value = build_ast(pair.next().unwrap());
+            Node::DeclareFunctionVariable(identifier.to_string(), Box::new(value))
+        }
+        Rule::DeclareIntegerStatement => {
+            let mut pair = pair.into_inner();
+            let identifier = pair.next().unwrap().as_str();
+            let value = build_ast(pair.next().unwrap());
+            Node::DeclareInteger(identifier.to_string(), Box::new(value))
+        }
         Rule::DeclareStringStatement => {
             let mut pair = pair.into_inner();
             let identifier = pair.next().unwrap().as_str();
             let value = build_ast(pair.next().unwrap());
             Node::DeclareString(identifier.to_string(), Box::new(value))
         }
+        Rule::DeclareListStatement => {
+            let mut pair = pair.into_inner();
+            let identifier = pair.next().unwrap().as_str();
+            Node::DeclareList(identifier.to_string())
+        }
+        Rule::DeclareMapStatement => {
+            let mut pair = pair.into_inner();
+            let identifier = pair.next().unwrap().as_str();
+            Node::DeclareMap(identifier.to_string())
+        }
+        Rule::NewRecordStatement => {
+            let mut pair = pair.into_inner();
+            let type_name = pair.next().unwrap().as_str();
+            let identifier = pair.next().unwrap().as_str();
+            Node::NewRecord(type_name.to_string(), identifier.to_string())
+        }
+        Rule::SetFieldStatement => {
+            let mut pair = pair.into_inner();
+            let identifier = pair.next().unwrap().as_str();
+            let field = pair.next().unwrap().as_str();
+            let value = build_ast(pair.next().unwrap());
+            Node::SetField(identifier.to_string(), field.to_string(), Box::new(value))
+        }
+        Rule::RemoveStatement => {
+            let mut pair = pair.into_inner();
+            let identifier = pair.next().unwrap().as_str();
+            let index = build_ast(pair.next().unwrap());
+            Node::Remove(identifier.to_string(), Box::new(index))
+        }
+        Rule::AppendStatement => {
+            let mut pair = pair.into_inner();
+            let identifier = pair.next().unwrap().as_str();
+            let value = build_ast(pair.next().unwrap());
+            Node::Append(identifier.to_string(), Box::new(value))
+        }
+        Rule::SetElementStatement => {
+            let mut pair = pair.into_inner();
+            let identifier = pair.next().unwrap().as_str();
+            let index = build_ast(pair.next().unwrap());
+            let value = build_ast(pair.next().unwrap());
+            Node::SetElement(identifier.to_string(), Box::new(index), Box::new(value))
+        }
         Rule::ReadBooleanStatement => {
             let mut pair = pair.into_inner();
             let identifier = pair.next().unwrap().as_str();
@@ -93,9 +240,40 @@ fn build_ast(pair: pest::iterators::Pair<Rule>) -> Node {
             let mut pair = pair.into_inner();
             Node::Print(Box::new(build_ast(pair.next().unwrap())))
         }
+        Rule::PrintLineStatement => {
+            let mut pair = pair.into_inner();
+            Node::PrintLine(Box::new(build_ast(pair.next().unwrap())))
+        }
+        Rule::FormatFixedStatement => {
+            let mut pair = pair.into_inner();
+            Node::FormatFixed(Box::new(build_ast(pair.next().unwrap())))
+        }
+        Rule::FormatScientificStatement => Node::FormatScientific,
+        Rule::FormatDefaultStatement => Node::FormatDefault,
+        Rule::FormatRomanStatement => Node::FormatRoman,
+        Rule::AssertStatement => {
+            let mut pair = pair.into_inner();
+            let value = pair.next().unwrap();
+            // Keep the source on one line for the failure message
+            let source = value
+                .as_str()
+                .split_whitespace()
+                .collect::<Vec<_>>()
+                .join(" ");
+            Node::Assert(Box::new(build_ast(value)), source)
+        }
+        Rule::BreakStatement => Node::Break,
+        Rule::ContinueStatement => Node::Continue,
+        Rule::ExitStatement => {
+            let mut pair = pair.into_inner();
+            Node::Exit(Box::new(build_ast(pair.next().unwrap())))
+        }
         Rule::ReturnStatement => {
             let mut pair = pair.into_inner();
-            Node::Return(Box::new(build_ast(pair.next().unwrap())))
+            match pair.next() {
+                Some(value) => Node::Return(Box::new(build_ast(value))),
+                None => Node::Return(Box::new(Node::Noop)),
+            }
         }
         Rule::ForStatement => {
             let mut pairs = pair.into_inner();
@@ -111,6 +289,20 @@ fn build_ast(pair: pest::iterators::Pair<Rule>) -> Node {
                 statements,
             )
         }
+        Rule::ForEachStatement => {
+            let mut pairs = pair.into_inner();
+            let value = build_ast(pairs.next().unwrap());
+            let identifier = pairs.next().unwrap().as_str();
+            let mut statements = Vec::<Node>::new();
+            for pair in pairs {
+                statements.push(build_ast(pair));
+            }
+            Node::ForEach(
+                Box::new(value),
+                Box::new(Node::Variable(identifier.to_string())),
+                statements,
+            )
+        }
         Rule::WhileStatement => {
             let mut pairs = pair.into_inner();
             let value = build_ast(pairs.next().unwrap());
@@ -136,7 +328,54 @@ fn build_ast(pair: pest::iterators::Pair<Rule>) -> Node {
             }
             Node::If(Box::new(value), if_statements, else_statements)
         }
+        Rule::TryStatement => {
+            let pairs = pair.into_inner();
+            let mut try_statements = Vec::<Node>::new();
+            let mut error_name = None;
+            let mut catch_statements = Vec::<Node>::new();
+            for pair in pairs {
+                if pair.as_rule() == Rule::CatchClause {
+                    for pair in pair.into_inner() {
+                        if pair.as_rule() == Rule::VariableName {
+                            error_name = Some(pair.as_str().to_string());
+                        } else {
+                            catch_statements.push(build_ast(pair));
+                        }
+                    }
+                    break;
+                }
+                try_statements.push(build_ast(pair));
+            }
+            Node::Try(try_statements, error_name, catch_statements)
+        }
         Rule::NotOperator => Node::Unary(UnaryOperation::Not),
+        Rule::LengthOperator => Node::Unary(UnaryOperation::Length),
+        Rule::ToIntegerOperator => Node::Unary(UnaryOperation::ToInteger),
+        Rule::ToFloatOperator => Node::Unary(UnaryOperation::ToFloat),
+        Rule::ToStringOperator => Node::Unary(UnaryOperation::ToString),
+        Rule::ToBooleanOperator => Node::Unary(UnaryOperation::ToBoolean),
+        Rule::ContainsKeyOperator => {
+            let mut pair = pair.into_inner();
+            Node::Binary(
+                BinaryOperation::ContainsKey,
+                Box::new(build_ast(pair.next().unwrap())),
+            )
+        }
+        Rule::FieldOperator => {
+            let mut pair = pair.into_inner();
+            let field = pair.next().unwrap().as_str();
+            Node::Binary(
+                BinaryOperation::Field,
+                Box::new(Node::String(field.to_string())),
+            )
+        }
+        Rule::IndexOperator => {
+            let mut pair = pair.into_inner();
+            Node::Binary(
+                BinaryOperation::Index,
+                Box::new(build_ast(pair.next().unwrap())),
+            )
+        }
         Rule::AddOperator => {
             let mut pair = pair.into_inner();
             Node::Binary(
@@ -219,30 +458,61 @@ fn build_ast(pair: pest::iterators::Pair<Rule>) -> Node {
             let bool = pair.as_rule() == Rule::True;
             Node::Boolean(bool)
         }
-        Rule::Float => {
-            let float = pair.as_str();
-            let float = match float {
-                "IX" => 9.0,
-                "VIII" => 8.0,
-                "VII" => 7.0,
-                "VI" => 6.0,
-                "V" => 5.0,
-                "IV" => 4.0,
-                "III" => 3.0,
-                "II" => 2.0,
-                "I" => 1.0,
-                _ => float.parse::<f32>().unwrap(),
+        Rule::Float => Node::Float(pair.as_str().parse::<f64>().unwrap()),
+        Rule::Integer => {
+            let pair = pair.into_inner().next().unwrap();
+            let integer = pair.as_str();
+            let integer = match pair.as_rule() {
+                // Checked to fit before the AST is built
+                Rule::HexInteger => parse_radix(integer, "0x", 16).unwrap(),
+                Rule::BinaryInteger => parse_radix(integer, "0b", 2).unwrap(),
+                Rule::RomanInteger => parse_roman(integer),
+                // Numbers too big for an integer are still read as floats, as they were before
+                _ => match integer.parse::<i64>() {
+                    Ok(integer) => integer,
+                    Err(_) => return Node::Float(integer.parse::<f64>().unwrap()),
+                },
             };
 
-            Node::Float(float)
+            Node::Integer(integer)
         }
         Rule::String => {
-            let pairs = pair.into_inner();
+            // Text between placeholders is kept whole, so plain strings stay a single string
+            let mut parts = Vec::<Node>::new();
             let mut string = "".to_string();
-            for pair in pairs {
-                string.push_str(build_string(pair).as_str());
+            for pair in pair.into_inner().flatten() {
+                match pair.as_rule() {
+                    Rule::Characters | Rule::Escape => string.push_str(build_string(pair).as_str()),
+                    Rule::Placeholder => {
+                        let name = pair.into_inner().next().unwrap().as_str();
+                        if !string.is_empty() {
+                            parts.push(Node::String(string));
+                            string = "".to_string();
+                        }
+                        parts.push(Node::Variable(name.to_string()));
+                    }
+                    _ => (),
+                }
+            }
+
+            if parts.is_empty() {
+                Node::String(string)
+            } else {
+                if !string.is_empty() {
+                    parts.push(Node::String(string));
+                }
+                Node::Interpolation(parts)
             }
-            Node::String(string)
+        }
+        Rule::FunctionValue => {
+            let name = pair.into_inner().next().unwrap().as_str();
+            Node::Function(name.to_string())
+        }
+        Rule::TypedParameter => {
+            let mut pair = pair.into_inner();
+            let identifier = pair.next().unwrap().as_str();
+            let type_name = pair.next().unwrap().as_str();
+            Node::TypedParameter(identifier.to_string(), Type::named(type_name))
         }
         Rule::VariableName => {
             let name = pair.as_str();
@@ -255,6 +525,24 @@ fn build_ast(pair: pest::iterators::Pair<Rule>) -> Node {
 fn build_function(pair: pest::iterators::Pair<Rule>, void: bool) -> Node {
     let mut pairs = pair.into_inner();
     let identifier = pairs.next().unwrap().as_str();
+    let (returns, parameters, body) = build_function_parts(pairs);
+    Node::DeclareFunction(identifier.to_string(), parameters, body, void, returns)
+}
+
+fn build_anonymous_function(pair: pest::iterators::Pair<Rule>, void: bool) -> Node {
+    let (returns, parameters, body) = build_function_parts(pair.into_inner());
+    Node::AnonymousFunction(parameters, body, void, returns)
+}
+
+/// The return type, parameters and body of a function, named or not.
+fn build_function_parts(
+    mut pairs: pest::iterators::Pairs<Rule>,
+) -> (Option<Type>, Vec<Node>, Vec<Node>) {
+    let mut returns = None;
+    if let Some(Rule::ReturnType) = pairs.peek().map(|pair| pair.as_rule()) {
+        let type_name = pairs.next().unwrap().into_inner().next().unwrap().as_str();
+        returns = Some(Type::named(type_name));
+    }
     let mut parameters = Vec::<Node>::new();
     let maybe_params = pairs.next().unwrap();
     if Rule::Parameters == maybe_params.as_rule() {
@@ -266,20 +554,11 @@ fn build_function(pair: pest::iterators::Pair<Rule>, void: bool) -> Node {
     for pair in pairs {
         body.push(build_ast(pair));
     }
-
-    Node::DeclareFunction(identifier.to_string(), parameters, body, void)
+    (returns, parameters, body)
 }
 
 fn build_string(pair: pest::iterators::Pair<Rule>) -> String {
     match pair.as_rule() {
-        Rule::Inner => {
-            let pairs = pair.into_inner();
-            let mut string = "".to_string();
-            for pair in pairs {
-                string.push_str(build_string(pair).as_str());
-            }
-            string
-        }
         Rule::Characters => pair.as_str().to_string(),
         Rule::Escape => match pair.as_str() {
             "\\\"" => "\"".to_string(),
@@ -288,21 +567,551 @@ fn build_string(pair: pest::iterators::Pair<Rule>) -> String {
             "\\n" => "\n".to_string(),
             "\\r" => "\r".to_string(),
             "\\t" => "\t".to_string(),
+            "\\{" => "{".to_string(),
+            "\\}" => "}".to_string(),
             _ => unreachable!(),
         },
         _ => unreachable!("String could not be parsed"),
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hello_there() {
+        let source = r#"
+        Do it!
+            The Sacred Jedi Texts! "Hello there"
+        May The Force be with you.
+        "#;
+        let ast = parse(source);
+        assert!(ast.is_ok());
+
+        assert_eq!(
+            ast.unwrap(),
+            vec![Node::Main(vec!(Node::Print(Box::new(Node::String(
+                "Hello there".to_string()
+            )))))]
+        );
+    }
+
+    #[test]
+    fn variable() {
+        let source = r#"
+        Do it!
+            Size matters not. jawa
+            Who, mesa? -13.2
+
+            The Sacred Jedi Texts! jawa
+        May The Force be with you.
+        "#;
+        let ast = parse(source);
+        assert!(ast.is_ok());
+
+        assert_eq!(
+            ast.unwrap(),
+            vec![Node::Main(vec!(
+                Node::DeclareFloat("jawa".to_string(), Box::new(Node::Float(-13.2)),),
+                Node::Print(Box::new(Node::Variable("jawa".to_string()))),
+            ))]
+        );
+
+        let source = r#"
+        Do it!
+            Yoda. You seek Yoda. ewok
+            Who, mesa? "Nub Nub"
+
+            The Sacred Jedi Texts! ewok
+        May The Force be with you.
+        "#;
+        let ast = parse(source);
+        assert!(ast.is_ok());
+
+        assert_eq!(
+            ast.unwrap(),
+            vec![Node::Main(vec!(
+                Node::DeclareString(
+                    "ewok".to_string(),
+                    Box::new(Node::String("Nub Nub".to_string()))
+                ),
+                Node::Print(Box::new(Node::Variable("ewok".to_string()))),
+            ))]
+        );
+
+        let source = r#"
+        Do it!
+            I am the Senate! darkSide
+            Who, mesa? From a certain point of view.
+
+            The Sacred Jedi Texts! darkSide
+        May The Force be with you.
+        "#;
+        let ast = parse(source);
+        assert!(ast.is_ok());
+
+        assert_eq!(
+            ast.unwrap(),
+            vec![Node::Main(vec!(
+                Node::DeclareBoolean("darkSide".to_string(), Box::new(Node::Boolean(true))),
+                Node::Print(Box::new(Node::Variable("darkSide".to_string()))),
+            ))]
+        );
+    }
+
+    #[test]
+    fn math() {
+        let source = r#"
+        Do it!
+            Size matters not. porg
+            Who, mesa? 4
+
+            What a piece of junk! porg
+                I am your father. porg
+                Your lightsabers will make a fine addition to my collection. 2.0
+                Proceed with the countdown. 1
+                There's too many of them! 3
+                Not to worry, we are still flying half a ship. 5
+                Unlimited power! 2
+                Never tell me the odds! 10
+                Your lightsabers will make a fine addition to my collection. V
+                Proceed with the countdown. II
+                There's too many of them! IX
+                Not to worry, we are still flying half a ship. IV
+                Unlimited power! III
+                Never tell me the odds! I
+            The garbage will do.
+
+            The Sacred Jedi Texts! porg
+        May The Force be with you.
+        "#;
+        let ast = parse(source);
+        assert!(ast.is_ok());
+
+        assert_eq!(
+            ast.unwrap(),
+            vec![Node::Main(vec!(
+                Node::DeclareFloat("porg".to_string(), Box::new(Node::Integer(4))),
+                Node::AssignVariable(
+                    "porg".to_string(),
+                    Box::new(Node::Variable("porg".to_string())),
+                    vec!(
+                        Node::Binary(BinaryOperation::Add, Box::new(Node::Float(2.0))),
+                        Node::Binary(BinaryOperation::Subtract, Box::new(Node::Integer(1))),
+                        Node::Binary(BinaryOperation::Multiply, Box::new(Node::Integer(3))),
+                        Node::Binary(BinaryOperation::Divide, Box::new(Node::Integer(5))),
+                        Node::Binary(BinaryOperation::Exponent, Box::new(Node::Integer(2))),
+                        Node::Binary(BinaryOperation::Modulus, Box::new(Node::Integer(10))),
+                        Node::Binary(BinaryOperation::Add, Box::new(Node::Integer(5))),
+                        Node::Binary(BinaryOperation::Subtract, Box::new(Node::Integer(2))),
+                        Node::Binary(BinaryOperation::Multiply, Box::new(Node::Integer(9))),
+                        Node::Binary(BinaryOperation::Divide, Box::new(Node::Integer(4))),
+                        Node::Binary(BinaryOperation::Exponent, Box::new(Node::Integer(3))),
+                        Node::Binary(BinaryOperation::Modulus, Box::new(Node::Integer(1))),
+                    )
+                ),
+                Node::Print(Box::new(Node::Variable("porg".to_string()))),
+            ))]
+        );
+    }
+
+    #[test]
+    fn grouping() {
+        let source = r#"
+        Do it!
+            What a piece of junk! fleet
+                I am your father. Hello there.
+                    Hello there. 2 General Kenobi.
+                    Your lightsabers will make a fine addition to my collection. 3
+                General Kenobi.
+                There's too many of them! Hello there.
+                    ships
+                    Proceed with the countdown. 1
+                General Kenobi.
+            The garbage will do.
+        May The Force be with you.
+        "#;
+        let ast = parse(source);
+        assert!(ast.is_ok());
+
+        assert_eq!(
+            ast.unwrap(),
+            vec![Node::Main(vec![Node::AssignVariable(
+                "fleet".to_string(),
+                Box::new(Node::Expression(
+                    Box::new(Node::Expression(Box::new(Node::Integer(2)), vec![])),
+                    vec![Node::Binary(
+                        BinaryOperation::Add,
+                        Box::new(Node::Integer(3))
+                    )]
+                )),
+                vec![Node::Binary(
+                    BinaryOperation::Multiply,
+                    Box::new(Node::Expression(
+                        Box::new(Node::Variable("ships".to_string())),
+                        vec![Node::Binary(
+                            BinaryOperation::Subtract,
+                            Box::new(Node::Integer(1))
+                        )]
+                    ))
+                )]
+            )])]
+        );
+    }
+
+    #[test]
+    fn expressions() {
+        let source = r#"
+        Do it!
+            Do, or do not. There is no try. x
+                Impressive. Most impressive. 10
+                The Sacred Jedi Texts! x
+                    There's too many of them! 2
+            You have failed me for the last time.
+
+            I have a bad feeling about this. Jump
+                I'll try spinning, that's a good trick. x
+                    Your lightsabers will make a fine addition to my collection. 1
+
+            Many Bothans died to bring us this information. x
+                I have a bad feeling about this. Jump
+                    I'll try spinning, that's a good trick. x
+                Your lightsabers will make a fine addition to my collection. 1
+            The garbage will do.
+        May The Force be with you.
+        "#;
+        let ast = parse(source);
+        assert!(ast.is_ok());
+
+        assert_eq!(
+            ast.unwrap(),
+            vec![Node::Main(vec![
+                Node::If(
+                    Box::new(Node::Expression(
+                        Box::new(Node::Variable("x".to_string())),
+                        vec![Node::Binary(
+                            BinaryOperation::GreaterThan,
+                            Box::new(Node::Integer(10))
+                        )]
+                    )),
+                    vec![Node::Print(Box::new(Node::Expression(
+                        Box::new(Node::Variable("x".to_string())),
+                        vec![Node::Binary(
+                            BinaryOperation::Multiply,
+                            Box::new(Node::Integer(2))
+                        )]
+                    )))],
+                    vec![]
+                ),
+                Node::CallFunction(
+                    "Jump".to_string(),
+                    vec![Node::Expression(
+                        Box::new(Node::Variable("x".to_string())),
+                        vec![Node::Binary(
+                            BinaryOperation::Add,
+                            Box::new(Node::Integer(1))
+                        )]
+                    )]
+                ),
+                // Operators after a call apply to what it returns
+                Node::AssignVariable(
+                    "x".to_string(),
+                    Box::new(Node::CallFunction(
+                        "Jump".to_string(),
+                        vec![Node::Variable("x".to_string())]
+                    )),
+                    vec![Node::Binary(
+                        BinaryOperation::Add,
+                        Box::new(Node::Integer(1))
+                    )]
+                ),
+            ])]
+        );
+    }
+
+    #[test]
+    fn initial_values() {
+        let source = r#"
+        Do it!
+            Only a Sith deals in absolutes. total
+            Who, mesa? x
+                There's too many of them! 2
+
+            Only a Sith deals in absolutes. jumps
+            Who, mesa? I have a bad feeling about this. Jump
+                I'll try spinning, that's a good trick. x
+            Your lightsabers will make a fine addition to my collection. 1
+        May The Force be with you.
+        "#;
+        let ast = parse(source);
+        assert!(ast.is_ok());
+
+        assert_eq!(
+            ast.unwrap(),
+            vec![Node::Main(vec![
+                Node::DeclareInteger(
+                    "total".to_string(),
+                    Box::new(Node::Expression(
+                        Box::new(Node::Variable("x".to_string())),
+                        vec![Node::Binary(
+                            BinaryOperation::Multiply,
+                            Box::new(Node::Integer(2))
+                        )]
+                    ))
+                ),
+                Node::DeclareInteger(
+                    "jumps".to_string(),
+                    Box::new(Node::Expression(
+                        Box::new(Node::CallFunction(
+                            "Jump".to_string(),
+                            vec![Node::Variable("x".to_string())]
+                        )),
+                        vec![Node::Binary(
+                            BinaryOperation::Add,
+                            Box::new(Node::Integer(1))
+                        )]
+                    ))
+                ),
+            ])]
+        );
+
+        // A call starts with I, which is also a roman numeral
+        let source = r#"
+        Do it!
+            Yoda. You seek Yoda. name
+            Who, mesa? I have a bad feeling about this. upper
+                I'll try spinning, that's a good trick. "Rey"
+
+            I am the Senate! ready
+            Who, mesa? I have a bad feeling about this. Ready
+        May The Force be with you.
+        "#;
+        let ast = parse(source);
+        assert!(ast.is_ok());
+
+        assert_eq!(
+            ast.unwrap(),
+            vec![Node::Main(vec![
+                Node::DeclareString(
+                    "name".to_string(),
+                    Box::new(Node::CallFunction(
+                        "upper".to_string(),
+                        vec![Node::String("Rey".to_string())]
+                    ))
+                ),
+                Node::DeclareBoolean(
+                    "ready".to_string(),
+                    Box::new(Node::CallFunction("Ready".to_string(), vec![]))
+                ),
+            ])]
+        );
+    }
+
+    #[test]
+    fn integers() {
+        let source = r#"
+        Do it!
+            Only a Sith deals in absolutes. clones
+            Who, mesa? 200000
+
+            What a piece of junk! clones
+                I am your father. clones
+                There's too many of them! IV
+                You must unlearn what you have learned.
+                Not to worry, we are still flying half a ship. 3.5
+                I'm just a simple man, trying to make my way in the universe.
+            The garbage will do.
+        May The Force be with you.
+        "#;
+        let ast = parse(source);
+        assert!(ast.is_ok());
+
+        assert_eq!(
+            ast.unwrap(),
+            vec![Node::Main(vec![
+                Node::DeclareInteger("clones".to_string(), Box::new(Node::Integer(200000))),
+                Node::AssignVariable(
+                    "clones".to_string(),
+                    Box::new(Node::Variable("clones".to_string())),
+                    vec![
+                        Node::Binary(BinaryOperation::Multiply, Box::new(Node::Integer(4))),
+                        Node::Unary(UnaryOperation::ToFloat),
+                        Node::Binary(BinaryOperation::Divide, Box::new(Node::Float(3.5))),
+                        Node::Unary(UnaryOperation::ToInteger),
+                    ]
+                ),
+            ])]
+        );
+    }
+
+    #[test]
+    fn numeric_literals() {
+        let source = r#"
+        Do it!
+            The Sacred Jedi Texts! 1.5e3
+            The Sacred Jedi Texts! -2E-2
+            The Sacred Jedi Texts! +7
+            The Sacred Jedi Texts! 0xFF
+            The Sacred Jedi Texts! -0x10
+            The Sacred Jedi Texts! 0b101
+            The Sacred Jedi Texts! XII
+            The Sacred Jedi Texts! XLII
+            The Sacred Jedi Texts! MCMXCIX
+            The Sacred Jedi Texts! IIII
+            The Sacred Jedi Texts! Ivan
+        May The Force be with you.
+        "#;
+        let ast = parse(source);
+        assert!(ast.is_ok());
+
+        assert_eq!(
+            ast.unwrap(),
+            vec![Node::Main(vec![
+                Node::Print(Box::new(Node::Float(1500.0))),
+                Node::Print(Box::new(Node::Float(-0.02))),
+                Node::Print(Box::new(Node::Integer(7))),
+                Node::Print(Box::new(Node::Integer(255))),
+                Node::Print(Box::new(Node::Integer(-16))),
+                Node::Print(Box::new(Node::Integer(5))),
+                Node::Print(Box::new(Node::Integer(12))),
+                Node::Print(Box::new(Node::Integer(42))),
+                Node::Print(Box::new(Node::Integer(1999))),
+                // Numerals that are not well formed are names
+                Node::Print(Box::new(Node::Variable("IIII".to_string()))),
+                Node::Print(Box::new(Node::Variable("Ivan".to_string()))),
+            ])]
+        );
+
+        // Numerals can't be declared as variables, as they would be read as numbers
+        for name in ["X", "MIX", "DIV"] {
+            let source = format!(
+                "Do it! Size matters not. {} Who, mesa? 5.5 May The Force be with you.",
+                name
+            );
+            assert!(parse(&source).is_err());
+        }
+    }
+
+    #[test]
+    fn oversized_integers() {
+        let source = r#"
+        Do it!
+            Size matters not. x
+            Who, mesa? 99999999999999999999
+        May The Force be with you.
+        "#;
+        let ast = parse(source);
+        assert!(ast.is_ok());
+
+        assert_eq!(
+            ast.unwrap(),
+            vec![Node::Main(vec![Node::DeclareFloat(
+                "x".to_string(),
+                Box::new(Node::Float(1e20))
+            )])]
+        );
+
+        // Hex and binary integers have no float to fall back to
+        let source = r#"
+        Do it!
+            The Sacred Jedi Texts! 0xFFFFFFFFFFFFFFFFFF
+        May The Force be with you.
+        "#;
+        assert!(parse(source).is_err());
+
+        let source = format!(
+            "Do it! The Sacred Jedi Texts! 0b1{} May The Force be with you.",
+            "0".repeat(64)
+        );
+        assert!(parse(&source).is_err());
+
+        let source = "Do it! The Sacred Jedi Texts! -0x8000000000000000 May The Force be with you.";
+        assert_eq!(
+            parse(source).unwrap(),
+            vec![Node::Main(vec![Node::Print(Box::new(Node::Integer(
+                i64::MIN
+            )))])]
+        );
+    }
+
+    #[test]
+    fn formatting() {
+        let source = r#"
+        Do it!
+            I am fluent in over six million forms of communication. II
+            Approximately three thousand seven hundred and twenty to one.
+            Everything's under control. Situation normal.
+            A long time ago in a galaxy far, far away....
+        May The Force be with you.
+        "#;
+        let ast = parse(source);
+        assert!(ast.is_ok());
+
+        assert_eq!(
+            ast.unwrap(),
+            vec![Node::Main(vec![
+                Node::FormatFixed(Box::new(Node::Integer(2))),
+                Node::FormatScientific,
+                Node::FormatDefault,
+                Node::FormatRoman,
+            ])]
+        );
+    }
+
+    #[test]
+    fn interpolation() {
+        let source = r#"
+        Do it!
+            Help me, Obi-Wan Kenobi. You're my only hope. "{count} of them, \{not\} {name}"
+            The Sacred Jedi Texts! "{name}"
+            The Sacred Jedi Texts! "\{\}"
+        May The Force be with you.
+        "#;
+        let ast = parse(source);
+        assert!(ast.is_ok());
+
+        assert_eq!(
+            ast.unwrap(),
+            vec![Node::Main(vec![
+                Node::PrintLine(Box::new(Node::Interpolation(vec![
+                    Node::Variable("count".to_string()),
+                    Node::String(" of them, {not} ".to_string()),
+                    Node::Variable("name".to_string()),
+                ]))),
+                Node::Print(Box::new(Node::Interpolation(vec![Node::Variable(
+                    "name".to_string()
+                )]))),
+                Node::Print(Box::new(Node::String("{}".to_string()))),
+            ])]
+        );
+
+        // Braces have to be escaped unless they are around a name
+        let source = r#"
+        Do it!
+            The Sacred Jedi Texts! "{}"
+        May The Force be with you.
+        "#;
+        assert!(parse(source).is_err());
+    }
 
     #[test]
-    fn hello_there() {
+    fn conversions() {
         let source = r#"
         Do it!
-            The Sacred Jedi Texts! "Hello there"
+            Try not.
+                The Sacred Jedi Texts! "42"
+                    I'm just a simple man, trying to make my way in the universe.
+                    I am C-3PO, human-cyborg relations.
+                    Search your feelings, you know it to be true.
+            It's not my fault! problem
+                The Sacred Jedi Texts! problem
+            Great, kid. Don't get cocky.
+
+            Try not.
+                Move along. Move along.
+            It's not my fault!
+            Great, kid. Don't get cocky.
         May The Force be with you.
         "#;
         let ast = parse(source);
@@ -310,20 +1119,35 @@ mod tests {
 
         assert_eq!(
             ast.unwrap(),
-            vec![Node::Main(vec!(Node::Print(Box::new(Node::String(
-                "Hello there".to_string()
-            )))))]
+            vec![Node::Main(vec![
+                Node::Try(
+                    vec![Node::Print(Box::new(Node::Expression(
+                        Box::new(Node::String("42".to_string())),
+                        vec![
+                            Node::Unary(UnaryOperation::ToInteger),
+                            Node::Unary(UnaryOperation::ToString),
+                            Node::Unary(UnaryOperation::ToBoolean),
+                        ]
+                    )))],
+                    Some("problem".to_string()),
+                    vec![Node::Print(Box::new(Node::Variable("problem".to_string())))],
+                ),
+                Node::Try(vec![], None, vec![]),
+            ])]
         );
     }
 
     #[test]
-    fn variable() {
+    fn constants() {
         let source = r#"
-        Do it!
-            Size matters not. jawa
-            Who, mesa? -13.2
+        It is unavoidable. It is your destiny.
+        Size matters not. taxRate
+        Who, mesa? 0.2
 
-            The Sacred Jedi Texts! jawa
+        Do it!
+            It is unavoidable. It is your destiny.
+            Only a Sith deals in absolutes. limit
+            Who, mesa? 10
         May The Force be with you.
         "#;
         let ast = parse(source);
@@ -331,18 +1155,32 @@ mod tests {
 
         assert_eq!(
             ast.unwrap(),
-            vec![Node::Main(vec!(
-                Node::DeclareFloat("jawa".to_string(), Box::new(Node::Float(-13.2)),),
-                Node::Print(Box::new(Node::Variable("jawa".to_string()))),
-            ))]
+            vec![
+                Node::DeclareConstant(Box::new(Node::DeclareFloat(
+                    "taxRate".to_string(),
+                    Box::new(Node::Float(0.2))
+                ))),
+                Node::Main(vec![Node::DeclareConstant(Box::new(Node::DeclareInteger(
+                    "limit".to_string(),
+                    Box::new(Node::Integer(10))
+                )))]),
+            ]
         );
+    }
 
+    #[test]
+    fn function_values() {
         let source = r#"
         Do it!
-            Yoda. You seek Yoda. ewok
-            Who, mesa? "Nub Nub"
+            You were the chosen one! op
+            Who, mesa? This is the weapon of a Jedi Knight. Double
 
-            The Sacred Jedi Texts! ewok
+            You were the chosen one! picked
+            Who, mesa? I have a bad feeling about this. Pick
+
+            I have a bad feeling about this. Apply
+                I'll try spinning, that's a good trick. This is the weapon of a Jedi Knight. Double
+                I'll try spinning, that's a good trick. 2
         May The Force be with you.
         "#;
         let ast = parse(source);
@@ -350,21 +1188,39 @@ mod tests {
 
         assert_eq!(
             ast.unwrap(),
-            vec![Node::Main(vec!(
-                Node::DeclareString(
-                    "ewok".to_string(),
-                    Box::new(Node::String("Nub Nub".to_string()))
+            vec![Node::Main(vec![
+                Node::DeclareFunctionVariable(
+                    "op".to_string(),
+                    Box::new(Node::Function("Double".to_string()))
                 ),
-                Node::Print(Box::new(Node::Variable("ewok".to_string()))),
-            ))]
+                Node::DeclareFunctionVariable(
+                    "picked".to_string(),
+                    Box::new(Node::CallFunction("Pick".to_string(), vec![]))
+                ),
+                Node::CallFunction(
+                    "Apply".to_string(),
+                    vec![Node::Function("Double".to_string()), Node::Integer(2)]
+                ),
+            ])]
         );
+    }
 
+    #[test]
+    fn anonymous_functions() {
         let source = r#"
         Do it!
-            I am the Senate! darkSide
-            Who, mesa? From a certain point of view.
-
-            The Sacred Jedi Texts! darkSide
+            You were the chosen one! addOne
+            Who, mesa? A surprise, to be sure, but a welcome one.
+            Now, that's a name I've not heard in a long time. A long time. x
+                You're all clear, kid. Now let's blow this thing and go home. x
+                    Your lightsabers will make a fine addition to my collection. 1
+            You cannot escape your destiny.
+
+            You were the chosen one! greet
+            Who, mesa? A surprise, to be sure, but a welcome one.
+            It's a trap!
+                The Sacred Jedi Texts! "Hello there"
+            You cannot escape your destiny.
         May The Force be with you.
         "#;
         let ast = parse(source);
@@ -372,37 +1228,45 @@ mod tests {
 
         assert_eq!(
             ast.unwrap(),
-            vec![Node::Main(vec!(
-                Node::DeclareBoolean("darkSide".to_string(), Box::new(Node::Boolean(true))),
-                Node::Print(Box::new(Node::Variable("darkSide".to_string()))),
-            ))]
+            vec![Node::Main(vec![
+                Node::DeclareFunctionVariable(
+                    "addOne".to_string(),
+                    Box::new(Node::AnonymousFunction(
+                        vec![Node::Variable("x".to_string())],
+                        vec![Node::Return(Box::new(Node::Expression(
+                            Box::new(Node::Variable("x".to_string())),
+                            vec![Node::Binary(
+                                BinaryOperation::Add,
+                                Box::new(Node::Integer(1))
+                            )]
+                        )))],
+                        false,
+                        None
+                    ))
+                ),
+                Node::DeclareFunctionVariable(
+                    "greet".to_string(),
+                    Box::new(Node::AnonymousFunction(
+                        vec![],
+                        vec![Node::Print(Box::new(Node::String(
+                            "Hello there".to_string()
+                        )))],
+                        true,
+                        None
+                    ))
+                ),
+            ])]
         );
     }
 
     #[test]
-    fn math() {
+    fn globals() {
         let source = r#"
-        Do it!
-            Size matters not. porg
-            Who, mesa? 4
-
-            What a piece of junk! porg
-                I am your father. porg
-                Your lightsabers will make a fine addition to my collection. 2.0
-                Proceed with the countdown. 1
-                There's too many of them! 3
-                Not to worry, we are still flying half a ship. 5
-                Unlimited power! 2
-                Never tell me the odds! 10
-                Your lightsabers will make a fine addition to my collection. V
-                Proceed with the countdown. II
-                There's too many of them! IX
-                Not to worry, we are still flying half a ship. IV
-                Unlimited power! III
-                Never tell me the odds! I
-            The garbage will do.
+        Only a Sith deals in absolutes. kills
+        Who, mesa? 0
 
-            The Sacred Jedi Texts! porg
+        Do it!
+            The Sacred Jedi Texts! kills
         May The Force be with you.
         "#;
         let ast = parse(source);
@@ -410,28 +1274,12 @@ mod tests {
 
         assert_eq!(
             ast.unwrap(),
-            vec![Node::Main(vec!(
-                Node::DeclareFloat("porg".to_string(), Box::new(Node::Float(4.0))),
-                Node::AssignVariable(
-                    "porg".to_string(),
-                    Box::new(Node::Variable("porg".to_string())),
-                    vec!(
-                        Node::Binary(BinaryOperation::Add, Box::new(Node::Float(2.0))),
-                        Node::Binary(BinaryOperation::Subtract, Box::new(Node::Float(1.0))),
-                        Node::Binary(BinaryOperation::Multiply, Box::new(Node::Float(3.0))),
-                        Node::Binary(BinaryOperation::Divide, Box::new(Node::Float(5.0))),
-                        Node::Binary(BinaryOperation::Exponent, Box::new(Node::Float(2.0))),
-                        Node::Binary(BinaryOperation::Modulus, Box::new(Node::Float(10.0))),
-                        Node::Binary(BinaryOperation::Add, Box::new(Node::Float(5.0))),
-                        Node::Binary(BinaryOperation::Subtract, Box::new(Node::Float(2.0))),
-                        Node::Binary(BinaryOperation::Multiply, Box::new(Node::Float(9.0))),
-                        Node::Binary(BinaryOperation::Divide, Box::new(Node::Float(4.0))),
-                        Node::Binary(BinaryOperation::Exponent, Box::new(Node::Float(3.0))),
-                        Node::Binary(BinaryOperation::Modulus, Box::new(Node::Float(1.0))),
-                    )
-                ),
-                Node::Print(Box::new(Node::Variable("porg".to_string()))),
-            ))]
+            vec![
+                Node::DeclareInteger("kills".to_string(), Box::new(Node::Integer(0))),
+                Node::Main(vec![Node::Print(Box::new(Node::Variable(
+                    "kills".to_string()
+                )))]),
+            ]
         );
     }
 
@@ -479,9 +1327,9 @@ mod tests {
         assert_eq!(
             ast.unwrap(),
             vec![Node::Main(vec!(
-                Node::DeclareFloat("anakin".to_string(), Box::new(Node::Float(27700.0))),
-                Node::DeclareFloat("luke".to_string(), Box::new(Node::Float(14500.0))),
-                Node::DeclareFloat("leia".to_string(), Box::new(Node::Float(14500.0))),
+                Node::DeclareFloat("anakin".to_string(), Box::new(Node::Integer(27700))),
+                Node::DeclareFloat("luke".to_string(), Box::new(Node::Integer(14500))),
+                Node::DeclareFloat("leia".to_string(), Box::new(Node::Integer(14500))),
                 Node::DeclareBoolean("midichlorian".to_string(), Box::new(Node::Boolean(false))),
                 Node::AssignVariable(
                     "midichlorian".to_string(),
@@ -609,7 +1457,7 @@ mod tests {
         assert_eq!(
             ast.unwrap(),
             vec![Node::Main(vec!(
-                Node::DeclareFloat("deathStars".to_string(), Box::new(Node::Float(3.0))),
+                Node::DeclareFloat("deathStars".to_string(), Box::new(Node::Integer(3))),
                 Node::While(
                     Box::new(Node::Variable("deathStars".to_string())),
                     vec![
@@ -619,7 +1467,7 @@ mod tests {
                             Box::new(Node::Variable("deathStars".to_string())),
                             vec!(Node::Binary(
                                 BinaryOperation::Subtract,
-                                Box::new(Node::Float(1.0))
+                                Box::new(Node::Integer(1))
                             ),)
                         )
                     ],
@@ -647,9 +1495,9 @@ mod tests {
         assert_eq!(
             ast.unwrap(),
             vec![Node::Main(vec!(
-                Node::DeclareFloat("deadYounglings".to_string(), Box::new(Node::Float(0.0))),
+                Node::DeclareFloat("deadYounglings".to_string(), Box::new(Node::Integer(0))),
                 Node::For(
-                    Box::new(Node::Float(10.0)),
+                    Box::new(Node::Integer(10)),
                     Box::new(Node::Variable("deadYounglings".to_string())),
                     vec!(Node::Print(Box::new(Node::Variable(
                         "deadYounglings".to_string()
@@ -679,8 +1527,8 @@ mod tests {
         assert_eq!(
             ast.unwrap(),
             vec![Node::Main(vec!(
-                Node::DeclareFloat("deadYounglings".to_string(), Box::new(Node::Float(0.0))),
-                Node::DeclareFloat("lightsaberSwings".to_string(), Box::new(Node::Float(10.0))),
+                Node::DeclareFloat("deadYounglings".to_string(), Box::new(Node::Integer(0))),
+                Node::DeclareFloat("lightsaberSwings".to_string(), Box::new(Node::Integer(10))),
                 Node::For(
                     Box::new(Node::Variable("lightsaberSwings".to_string())),
                     Box::new(Node::Variable("deadYounglings".to_string())),
@@ -692,6 +1540,195 @@ mod tests {
         );
     }
 
+    #[test]
+    fn loop_control() {
+        let source = r#"
+        Do it!
+            Size matters not. jump
+            Who, mesa? 0
+
+            For over a thousand generations. 10
+            Let the Wookiee win. jump
+                Stay on target.
+                Punch it!
+            It is clear to me now the Republic no longer functions.
+        May The Force be with you.
+        "#;
+        let ast = parse(source);
+        assert!(ast.is_ok());
+
+        assert_eq!(
+            ast.unwrap(),
+            vec![Node::Main(vec![
+                Node::DeclareFloat("jump".to_string(), Box::new(Node::Integer(0))),
+                Node::For(
+                    Box::new(Node::Integer(10)),
+                    Box::new(Node::Variable("jump".to_string())),
+                    vec![Node::Continue, Node::Break]
+                ),
+            ])]
+        );
+    }
+
+    #[test]
+    fn lists() {
+        let source = r#"
+        Do it!
+            This is getting out of hand! Now there are two of them! clones
+            Another happy landing. clones I am your father. 99
+            I've made a lot of special modifications myself. clones
+                Red Five standing by. 0
+                I am your father. 5555
+
+            What a piece of junk! squad
+                I am your father. clones
+                Red Five standing by. 0
+            The garbage will do.
+
+            All of them. clones
+            Let the Wookiee win. clone
+                The Sacred Jedi Texts! clone
+            It is clear to me now the Republic no longer functions.
+
+            The Sacred Jedi Texts! clones
+                Look at the size of that thing!
+        May The Force be with you.
+        "#;
+        let ast = parse(source);
+        assert!(ast.is_ok());
+
+        assert_eq!(
+            ast.unwrap(),
+            vec![Node::Main(vec![
+                Node::DeclareList("clones".to_string()),
+                Node::Append("clones".to_string(), Box::new(Node::Integer(99))),
+                Node::SetElement(
+                    "clones".to_string(),
+                    Box::new(Node::Integer(0)),
+                    Box::new(Node::Integer(5555))
+                ),
+                Node::AssignVariable(
+                    "squad".to_string(),
+                    Box::new(Node::Variable("clones".to_string())),
+                    vec![Node::Binary(
+                        BinaryOperation::Index,
+                        Box::new(Node::Integer(0))
+                    )]
+                ),
+                Node::ForEach(
+                    Box::new(Node::Variable("clones".to_string())),
+                    Box::new(Node::Variable("clone".to_string())),
+                    vec![Node::Print(Box::new(Node::Variable("clone".to_string())))]
+                ),
+                Node::Print(Box::new(Node::Expression(
+                    Box::new(Node::Variable("clones".to_string())),
+                    vec![Node::Unary(UnaryOperation::Length)]
+                ))),
+            ])]
+        );
+    }
+
+    #[test]
+    fn maps() {
+        let source = r#"
+        Do it!
+            It's a map to Luke Skywalker. map
+            I've made a lot of special modifications myself. map
+                Red Five standing by. "Luke"
+                I am your father. "Jakku"
+            Lost a planet, Master Obi-Wan has. map Red Five standing by. "Kamino"
+
+            The Sacred Jedi Texts! map
+                If an item does not appear in our records, it does not exist. "Kamino"
+        May The Force be with you.
+        "#;
+        let ast = parse(source);
+        assert!(ast.is_ok());
+
+        assert_eq!(
+            ast.unwrap(),
+            vec![Node::Main(vec![
+                Node::DeclareMap("map".to_string()),
+                Node::SetElement(
+                    "map".to_string(),
+                    Box::new(Node::String("Luke".to_string())),
+                    Box::new(Node::String("Jakku".to_string()))
+                ),
+                Node::Remove(
+                    "map".to_string(),
+                    Box::new(Node::String("Kamino".to_string()))
+                ),
+                Node::Print(Box::new(Node::Expression(
+                    Box::new(Node::Variable("map".to_string())),
+                    vec![Node::Binary(
+                        BinaryOperation::ContainsKey,
+                        Box::new(Node::String("Kamino".to_string()))
+                    )]
+                ))),
+            ])]
+        );
+    }
+
+    #[test]
+    fn records() {
+        let source = r#"
+        She may not look like much, but she's got it where it counts, kid. Ship
+            Yoda. You seek Yoda. name
+            Who, mesa? "Falcon"
+
+            Size matters not. speed
+            Who, mesa? 1.5
+
+            Only a Sith deals in absolutes. crew
+            Who, mesa? 4
+        Hear me, baby? Hold together.
+
+        Do it!
+            You came in that thing? Ship falcon
+            I've made a lot of special modifications myself. falcon
+                What have we here? speed
+                I am your father. 0.5
+
+            The Sacred Jedi Texts! falcon
+                What have we here? name
+        May The Force be with you.
+        "#;
+        let ast = parse(source);
+        assert!(ast.is_ok());
+
+        assert_eq!(
+            ast.unwrap(),
+            vec![
+                Node::DeclareRecord(
+                    "Ship".to_string(),
+                    vec![
+                        Node::DeclareString(
+                            "name".to_string(),
+                            Box::new(Node::String("Falcon".to_string()))
+                        ),
+                        Node::DeclareFloat("speed".to_string(), Box::new(Node::Float(1.5))),
+                        Node::DeclareInteger("crew".to_string(), Box::new(Node::Integer(4))),
+                    ]
+                ),
+                Node::Main(vec![
+                    Node::NewRecord("Ship".to_string(), "falcon".to_string()),
+                    Node::SetField(
+                        "falcon".to_string(),
+                        "speed".to_string(),
+                        Box::new(Node::Float(0.5))
+                    ),
+                    Node::Print(Box::new(Node::Expression(
+                        Box::new(Node::Variable("falcon".to_string())),
+                        vec![Node::Binary(
+                            BinaryOperation::Field,
+                            Box::new(Node::String("name".to_string()))
+                        )]
+                    ))),
+                ]),
+            ]
+        );
+    }
+
     #[test]
     fn if_else() {
         let source = r#"
@@ -746,7 +1783,8 @@ mod tests {
                         Node::Print(Box::new(Node::Variable("planet".to_string()))),
                         Node::Print(Box::new(Node::String("Deathstar noise".to_string())))
                     ),
-                    true
+                    true,
+                    None
                 ),
                 Node::Main(vec!(Node::CallFunction(
                     "NameTheSystem".to_string(),
@@ -799,14 +1837,15 @@ mod tests {
                             vec![
                                 Node::Binary(
                                     BinaryOperation::Modulus,
-                                    Box::new(Node::Float(3720.0))
+                                    Box::new(Node::Integer(3720))
                                 ),
-                                Node::Binary(BinaryOperation::Equal, Box::new(Node::Float(0.0))),
+                                Node::Binary(BinaryOperation::Equal, Box::new(Node::Integer(0))),
                             ]
                         ),
                         Node::Return(Box::new(Node::Variable("survive".to_string())))
                     ],
-                    false
+                    false,
+                    None
                 ),
                 Node::Main(vec![
                     Node::DeclareBoolean("survive".to_string(), Box::new(Node::Boolean(false))),
@@ -814,7 +1853,7 @@ mod tests {
                         "survive".to_string(),
                         Box::new(Node::CallFunction(
                             "TheOdds".to_string(),
-                            vec!(Node::Float(52.0))
+                            vec!(Node::Integer(52))
                         )),
                         vec!()
                     ),
@@ -824,6 +1863,94 @@ mod tests {
         );
     }
 
+    #[test]
+    fn typed_functions() {
+        let source = r#"
+        This is where the fun begins. Half
+        Rebellions are built on hope. float
+        Now, that's a name I've not heard in a long time. A long time. x
+        Rebellions are built on hope. integer
+        Now, that's a name I've not heard in a long time. A long time. label
+            You're all clear, kid. Now let's blow this thing and go home. x
+                Not to worry, we are still flying half a ship. 2
+        You cannot escape your destiny.
+        "#;
+        let ast = parse(source);
+        assert!(ast.is_ok());
+
+        assert_eq!(
+            ast.unwrap(),
+            vec![Node::DeclareFunction(
+                "Half".to_string(),
+                vec![
+                    Node::TypedParameter("x".to_string(), Type::Integer),
+                    Node::Variable("label".to_string())
+                ],
+                vec![Node::Return(Box::new(Node::Expression(
+                    Box::new(Node::Variable("x".to_string())),
+                    vec![Node::Binary(
+                        BinaryOperation::Divide,
+                        Box::new(Node::Integer(2))
+                    )]
+                )))],
+                false,
+                Some(Type::Float)
+            )]
+        );
+    }
+
+    #[test]
+    fn early_return() {
+        let source = r#"
+        This is where the fun begins. Search
+        Now, that's a name I've not heard in a long time. A long time. system
+            Do, or do not. There is no try. system
+                You're all clear, kid. Now let's blow this thing and go home. system
+            You have failed me for the last time.
+
+            You're all clear, kid. Now let's blow this thing and go home. 0
+        You cannot escape your destiny.
+
+        This is where the fun begins. Leave
+        It's a trap!
+            You're all clear, kid. Now let's blow this thing and go home.
+            The Sacred Jedi Texts! "Never printed"
+        You cannot escape your destiny.
+        "#;
+        let ast = parse(source);
+        assert!(ast.is_ok());
+
+        assert_eq!(
+            ast.unwrap(),
+            vec![
+                Node::DeclareFunction(
+                    "Search".to_string(),
+                    vec![Node::Variable("system".to_string())],
+                    vec![
+                        Node::If(
+                            Box::new(Node::Variable("system".to_string())),
+                            vec![Node::Return(Box::new(Node::Variable("system".to_string())))],
+                            vec![]
+                        ),
+                        Node::Return(Box::new(Node::Integer(0))),
+                    ],
+                    false,
+                    None
+                ),
+                Node::DeclareFunction(
+                    "Leave".to_string(),
+                    vec![],
+                    vec![
+                        Node::Return(Box::new(Node::Noop)),
+                        Node::Print(Box::new(Node::String("Never printed".to_string()))),
+                    ],
+                    true,
+                    None
+                ),
+            ]
+        );
+    }
+
     #[test]
     fn input() {
         let source = r#"
@@ -893,6 +2020,47 @@ mod tests {
         );
     }
 
+    #[test]
+    fn assert() {
+        let source = r#"
+        Do it!
+            I am the Senate! faith
+            Who, mesa? From a certain point of view.
+
+            I find your lack of faith disturbing. faith
+        May The Force be with you.
+        "#;
+        let ast = parse(source);
+        assert!(ast.is_ok());
+
+        assert_eq!(
+            ast.unwrap(),
+            vec![Node::Main(vec![
+                Node::DeclareBoolean("faith".to_string(), Box::new(Node::Boolean(true))),
+                Node::Assert(
+                    Box::new(Node::Variable("faith".to_string())),
+                    "faith".to_string()
+                ),
+            ])]
+        );
+    }
+
+    #[test]
+    fn exit() {
+        let source = r#"
+        Do it!
+            It's over, Anakin! I have the high ground. 3
+        May The Force be with you.
+        "#;
+        let ast = parse(source);
+        assert!(ast.is_ok());
+
+        assert_eq!(
+            ast.unwrap(),
+            vec![Node::Main(vec![Node::Exit(Box::new(Node::Integer(3)))])]
+        );
+    }
+
     #[test]
     fn other() {
         let source = r#"
@@ -0,0 +1,473 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    thread,
+};
+
+use crate::{
+    ast::Node,
+    checker,
+    error::Error,
+    interpreter::{self, Options},
+    parser,
+};
+
+/// The prefix used in a header comment to provide a line of standard in.
+const INPUT_HEADER: &str = "input:";
+
+/// The quote that declares a function, used to spot test functions in a file that doesn't parse.
+const DECLARE_FUNCTION: &str = "This is where the fun begins.";
+
+struct TestCase {
+    name: String,
+    program: Result<Vec<Node>, Error>,
+    input: String,
+    expected: Option<String>,
+}
+
+enum Outcome {
+    Pass,
+    Fail(String),
+}
+
+/// Runs every `.force` file under `path` that has an adjacent `.out` file, along with every function
+/// whose name starts with `test`, and reports the results.
+pub fn run(path: &str, options: &Options) -> Result<(), Error> {
+    let cases = collect(Path::new(path))?;
+    if cases.is_empty() {
+        return Err(Error::Runtime(format!("No tests found in {}", path)));
+    }
+
+    // Each case gets its own interpreter, so they can all run at once
+    let handles: Vec<_> = cases
+        .into_iter()
+        .map(|case| {
+            let name = case.name.clone();
+            let options = options.clone();
+            (name, thread::spawn(move || run_case(&case, &options)))
+        })
+        .collect();
+
+    let mut passed = 0;
+    let mut failed = 0;
+    for (name, handle) in handles {
+        let outcome = handle
+            .join()
+            .unwrap_or_else(|_| Outcome::Fail("The interpreter panicked".to_string()));
+        match outcome {
+            Outcome::Pass => {
+                passed += 1;
+                println!("test {} ... ok", name);
+            }
+            Outcome::Fail(reason) => {
+                failed += 1;
+                println!("test {} ... FAILED", name);
+                println!("{}", reason);
+            }
+        }
+    }
+
+    println!("\n{} passed; {} failed", passed, failed);
+    if failed > 0 {
+        Err(Error::Runtime(format!("{} test(s) failed", failed)))
+    } else {
+        Ok(())
+    }
+}
+
+fn collect(path: &Path) -> Result<Vec<TestCase>, Error> {
+    let mut files = Vec::new();
+    find_sources(path, &mut files)?;
+    files.sort();
+
+    let mut cases = Vec::new();
+    for file in files {
+        let source = read_file(&file)?;
+        let input = match fs::read_to_string(file.with_extension("in")) {
+            Ok(input) => input,
+            Err(_) => header_input(&source),
+        };
+        let expected = fs::read_to_string(file.with_extension("out")).ok();
+
+        cases.append(&mut file_cases(
+            file.display().to_string(),
+            &source,
+            input,
+            expected,
+        ));
+    }
+    Ok(cases)
+}
+
+/// A file's output is a test when there is expected output for it, and each of its test functions is
+/// a separate test. A file that doesn't parse or check is a single failing test, unless it wouldn't
+/// have been tested at all.
+fn file_cases(
+    name: String,
+    source: &str,
+    input: String,
+    expected: Option<String>,
+) -> Vec<TestCase> {
+    let ast = match parser::parse(source) {
+        Ok(ast) => ast,
+        Err(_) if expected.is_none() && !declares_test(source) => return Vec::new(),
+        Err(e) => {
+            return vec![TestCase {
+                name,
+                program: Err(Error::Parse(e.to_string())),
+                input,
+                expected,
+            }]
+        }
+    };
+
+    // Test functions run on their own, with everything but main declared
+    let functions: Vec<Node> = ast
+        .iter()
+        .filter(|node| !matches!(node, Node::Main(_)))
+        .cloned()
+        .collect();
+
+    let mut cases = Vec::new();
+    for node in &functions {
+        if let Node::DeclareFunction(function_name, parameters, _, _, _) = node {
+            if !function_name.starts_with("test") {
+                continue;
+            }
+
+            // There is nothing to pass a test function, so one with parameters fails
+            let program = if parameters.is_empty() {
+                let mut program = functions.clone();
+                program.push(Node::Main(vec![Node::CallFunction(
+                    function_name.clone(),
+                    Vec::new(),
+                )]));
+                Ok(program)
+            } else {
+                Err(Error::Type(format!(
+                    "Test functions take no parameters, but {} takes {}",
+                    function_name,
+                    parameters.len()
+                )))
+            };
+            cases.push(TestCase {
+                name: format!("{}::{}", name, function_name),
+                program,
+                input: input.clone(),
+                expected: None,
+            });
+        }
+    }
+
+    if let Err(e) = checker::check(&ast) {
+        if expected.is_none() && cases.is_empty() {
+            return Vec::new();
+        }
+        return vec![TestCase {
+            name,
+            program: Err(e),
+            input,
+            expected,
+        }];
+    }
+
+    if expected.is_some() {
+        cases.insert(
+            0,
+            TestCase {
+                name,
+                program: Ok(ast),
+                input,
+                expected,
+            },
+        );
+    }
+    cases
+}
+
+/// Whether the source looks like it declares a test function, for when it can't be parsed.
+fn declares_test(source: &str) -> bool {
+    source
+        .split(DECLARE_FUNCTION)
+        .skip(1)
+        .any(|rest| rest.trim_start().starts_with("test"))
+}
+
+fn find_sources(path: &Path, files: &mut Vec<PathBuf>) -> Result<(), Error> {
+    if path.is_file() {
+        files.push(path.to_path_buf());
+        return Ok(());
+    }
+
+    let entries = fs::read_dir(path)
+        .map_err(|_| Error::Io(format!("{} could not be read", path.display())))?;
+    for entry in entries {
+        let entry = entry.map_err(|x| Error::Io(x.to_string()))?;
+        let entry_path = entry.path();
+        if entry_path.is_dir() {
+            find_sources(&entry_path, files)?;
+        } else if entry_path.extension() == Some("force".as_ref()) {
+            files.push(entry_path);
+        }
+    }
+    Ok(())
+}
+
+fn read_file(path: &Path) -> Result<String, Error> {
+    fs::read_to_string(path).map_err(|_| Error::Io(format!("{} could not be read", path.display())))
+}
+
+/// Collects the standard in provided by leading comments, such as `<(-.-)> input: 42`.
+fn header_input(source: &str) -> String {
+    let mut input = String::new();
+    for line in source.lines().map(str::trim) {
+        if line.is_empty() {
+            continue;
+        }
+
+        let comment = ["|-o-|", ":><:", "<(-.-)>"]
+            .iter()
+            .find_map(|marker| line.strip_prefix(marker));
+        let comment = match comment {
+            Some(comment) => comment.trim_start(),
+            // The header ends at the first line of code
+            None => break,
+        };
+
+        if let Some(value) = comment.strip_prefix(INPUT_HEADER) {
+            input.push_str(value.strip_prefix(' ').unwrap_or(value));
+            input.push('\n');
+        }
+    }
+    input
+}
+
+fn run_case(case: &TestCase, options: &Options) -> Outcome {
+    let ast = match &case.program {
+        Ok(ast) => ast.clone(),
+        Err(e) => return Outcome::Fail(e.to_string()),
+    };
+
+    let mut output = Vec::new();
+    match interpreter::evaluate(ast, case.input.as_bytes(), &mut output, options) {
+        Ok(0) => (),
+        Ok(status) => return Outcome::Fail(format!("Exited with status {}", status)),
+        Err(e) => return Outcome::Fail(e.to_string()),
+    }
+
+    let expected = match &case.expected {
+        Some(expected) => expected,
+        None => return Outcome::Pass,
+    };
+
+    let actual = String::from_utf8_lossy(&output);
+    if normalize(&actual) == normalize(expected) {
+        Outcome::Pass
+    } else {
+        Outcome::Fail(diff(&normalize(expected), &normalize(&actual)))
+    }
+}
+
+/// Ignores line ending style and trailing new lines, which editors like to change.
+fn normalize(output: &str) -> String {
+    output
+        .replace("\r\n", "\n")
+        .trim_end_matches('\n')
+        .to_string()
+}
+
+/// A line based diff, marking expected lines with `-` and actual lines with `+`.
+fn diff(expected: &str, actual: &str) -> String {
+    let expected: Vec<&str> = expected.lines().collect();
+    let actual: Vec<&str> = actual.lines().collect();
+
+    // Longest common subsequence of lines, built from the end
+    let mut lengths = vec![vec![0; actual.len() + 1]; expected.len() + 1];
+    for i in (0..expected.len()).rev() {
+        for j in (0..actual.len()).rev() {
+            lengths[i][j] = if expected[i] == actual[j] {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+
+    let mut lines = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < expected.len() || j < actual.len() {
+        if i < expected.len() && j < actual.len() && expected[i] == actual[j] {
+            lines.push(format!("  {}", expected[i]));
+            i += 1;
+            j += 1;
+        } else if j == actual.len()
+            || (i < expected.len() && lengths[i + 1][j] >= lengths[i][j + 1])
+        {
+            lines.push(format!("- {}", expected[i]));
+            i += 1;
+        } else {
+            lines.push(format!("+ {}", actual[j]));
+            j += 1;
+        }
+    }
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn input_header() {
+        let source = r#"
+        <(-.-)> input: 3
+        |-o-| The second line
+        :><: input:4
+        Do it!
+            <(-.-)> input: ignored
+        May The Force be with you.
+        "#;
+        assert_eq!(header_input(source), "3\n4\n");
+
+        let source = r#"
+        Do it!
+        May The Force be with you.
+        "#;
+        assert_eq!(header_input(source), "");
+    }
+
+    #[test]
+    fn output_diff() {
+        assert_eq!(diff("a\nb\nc", "a\nb\nc"), "  a\n  b\n  c");
+        assert_eq!(diff("a\nb\nc", "a\nx\nc"), "  a\n- b\n+ x\n  c");
+        assert_eq!(diff("a", "a\nb"), "  a\n+ b");
+        assert_eq!(diff("a\nb", ""), "- a\n- b");
+    }
+
+    #[test]
+    fn output_cases() {
+        let source = r#"
+        Do it!
+            Yoda. You seek Yoda. ewok
+            Who, mesa? ""
+            Looking? Found someone, you have, I would say. ewok
+            The Sacred Jedi Texts! ewok
+        May The Force be with you.
+        "#;
+
+        let cases = file_cases(
+            "echo".to_string(),
+            source,
+            "Wicket\n".to_string(),
+            Some("Wicket\n".to_string()),
+        );
+        assert_eq!(cases.len(), 1);
+        assert!(matches!(
+            run_case(&cases[0], &Options::default()),
+            Outcome::Pass
+        ));
+
+        let cases = file_cases(
+            "echo".to_string(),
+            source,
+            "Wicket\n".to_string(),
+            Some("Teebo\n".to_string()),
+        );
+        match run_case(&cases[0], &Options::default()) {
+            Outcome::Fail(reason) => assert_eq!(reason, "- Teebo\n+ Wicket"),
+            Outcome::Pass => panic!("Output should not match"),
+        }
+
+        // Not a test without expected output or test functions
+        let cases = file_cases("echo".to_string(), source, "".to_string(), None);
+        assert!(cases.is_empty());
+
+        // Even when it doesn't parse or check
+        let broken = "Do it! Punch it! May The Force be with you.";
+        let unparsable = "Do it! May The Force";
+        for source in [broken, unparsable] {
+            let cases = file_cases("broken".to_string(), source, "".to_string(), None);
+            assert!(cases.is_empty());
+
+            let cases = file_cases(
+                "broken".to_string(),
+                source,
+                "".to_string(),
+                Some("".to_string()),
+            );
+            assert_eq!(cases.len(), 1);
+            assert!(matches!(
+                run_case(&cases[0], &Options::default()),
+                Outcome::Fail(_)
+            ));
+        }
+
+        let source = "This is where the fun begins. testBroken It's a trap!";
+        let cases = file_cases("broken".to_string(), source, "".to_string(), None);
+        assert_eq!(cases.len(), 1);
+        assert_eq!(cases[0].name, "broken");
+    }
+
+    #[test]
+    fn function_cases() {
+        let source = r#"
+        This is where the fun begins. testFaith
+        It's a trap!
+            I find your lack of faith disturbing. From a certain point of view.
+        You cannot escape your destiny.
+
+        This is where the fun begins. testDoubt
+        It's a trap!
+            I find your lack of faith disturbing. That's impossible!
+        You cannot escape your destiny.
+
+        This is where the fun begins. helper
+        It's a trap!
+            I find your lack of faith disturbing. That's impossible!
+        You cannot escape your destiny.
+        "#;
+
+        let cases = file_cases("faith".to_string(), source, "".to_string(), None);
+        assert_eq!(cases.len(), 2);
+        assert_eq!(cases[0].name, "faith::testFaith");
+        assert!(matches!(
+            run_case(&cases[0], &Options::default()),
+            Outcome::Pass
+        ));
+        assert_eq!(cases[1].name, "faith::testDoubt");
+        assert!(matches!(
+            run_case(&cases[1], &Options::default()),
+            Outcome::Fail(_)
+        ));
+
+        let source = r#"
+        This is where the fun begins. testOdds
+        Now, that's a name I've not heard in a long time. A long time. odds
+        It's a trap!
+            I find your lack of faith disturbing. From a certain point of view.
+        You cannot escape your destiny.
+        "#;
+
+        let cases = file_cases("odds".to_string(), source, "".to_string(), None);
+        assert_eq!(cases.len(), 1);
+        match run_case(&cases[0], &Options::default()) {
+            Outcome::Fail(reason) => assert_eq!(
+                reason,
+                "Type error: Test functions take no parameters, but testOdds takes 1"
+            ),
+            Outcome::Pass => panic!("Test functions should not take parameters"),
+        }
+    }
+
+    #[test]
+    fn examples() {
+        let cases = collect(Path::new("examples")).expect("Examples not found");
+        assert!(!cases.is_empty());
+
+        for case in cases {
+            if let Outcome::Fail(reason) = run_case(&case, &Options::default()) {
+                panic!("{} failed:\n{}", case.name, reason);
+            }
+        }
+    }
+}